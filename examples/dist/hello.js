@@ -1 +1,5 @@
 window.webkit.messageHandlers.WinitMessageHandler.postMessage('Hello there!');
+
+fetch('/api/greeting/winit')
+    .then(response => response.text())
+    .then(greeting => window.webkit.messageHandlers.WinitMessageHandler.postMessage(greeting));
@@ -1,6 +1,9 @@
 extern crate winit;
 extern crate winit_webview;
 
+#[cfg(all(target_os = "linux", not(feature = "mock")))]
+use std::time::{Duration, Instant};
+
 use winit::{
     event::{Event, WindowEvent},
     event_loop::{ControlFlow, EventLoop},
@@ -9,7 +12,7 @@ use winit::{
 };
 use winit_webview as webview;
 
-const HTML: &'static str = include_str!("hello.html");
+const HTML: &str = include_str!("hello.html");
 
 pub fn main() {
     let mut event_loop = EventLoop::with_user_event();
@@ -30,7 +33,7 @@ pub fn main() {
                 event: WindowEvent::CloseRequested,
                 window_id,
             } if window_id == web_view.window.id() => *control_flow = ControlFlow::Exit,
-            Event::UserEvent(webview::Event::Navigation(webview::NavigationEvent::Finish)) => {
+            Event::UserEvent(webview::Event::Navigation(webview::NavigationEvent::Finish(_))) => {
                 if let Some(title) = web_view.title() {
                     web_view.window.set_title(&title);
                 }
@@ -38,6 +41,14 @@ pub fn main() {
             Event::UserEvent(evt) => {
                 println!("{:?}", evt);
             }
+            #[cfg(all(target_os = "linux", not(feature = "mock")))]
+            Event::RedrawEventsCleared => {
+                use webview::platform::linux::WebViewExtLinux;
+
+                // GTK runs its own event queue, so poll it alongside winit's
+                web_view.pump_events();
+                *control_flow = ControlFlow::WaitUntil(Instant::now() + Duration::from_millis(16));
+            }
             _ => (),
         }
     });
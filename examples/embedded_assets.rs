@@ -0,0 +1,65 @@
+extern crate winit;
+extern crate winit_webview;
+
+#[cfg(all(target_os = "linux", not(feature = "mock")))]
+use std::time::{Duration, Instant};
+
+use winit::{
+    event::{Event, WindowEvent},
+    event_loop::{ControlFlow, EventLoop},
+    platform::run_return::EventLoopExtRunReturn,
+    window::WindowBuilder,
+};
+use winit_webview as webview;
+
+pub fn main() {
+    #[cfg(all(target_os = "linux", not(feature = "mock")))]
+    use webview::platform::linux::WebViewBuilderExtLinux;
+    #[cfg(all(target_os = "macos", not(feature = "mock")))]
+    use webview::platform::macos::WebViewBuilderExtMacOS;
+
+    let mut event_loop = EventLoop::with_user_event();
+
+    let window = WindowBuilder::new()
+        .with_inner_size(winit::dpi::LogicalSize::new(640.0, 480.0))
+        .build(&event_loop)
+        .unwrap();
+
+    // The assets are compiled into the binary, so it runs from any directory
+    static ASSETS: webview::request::EmbeddedDir = webview::include_assets!("examples/dist");
+
+    let builder = webview::WebViewBuilder::with_request_handler(webview::request::Embedded::new(&ASSETS));
+    #[cfg(not(feature = "mock"))]
+    let builder = builder.with_debug(true);
+
+    let mut web_view = builder.build(event_loop.create_proxy(), window);
+    web_view.navigate(webview::NavigationTarget::Url("winit:///index.html"));
+
+    event_loop.run_return(move |event, _, control_flow| {
+        *control_flow = ControlFlow::Wait;
+
+        match event {
+            Event::WindowEvent {
+                event: WindowEvent::CloseRequested,
+                window_id,
+            } if window_id == web_view.window.id() => *control_flow = ControlFlow::Exit,
+            Event::UserEvent(webview::Event::Navigation(webview::NavigationEvent::Finish(_))) => {
+                if let Some(title) = web_view.title() {
+                    web_view.window.set_title(&title);
+                }
+            }
+            Event::UserEvent(evt) => {
+                println!("{:?}", evt);
+            }
+            #[cfg(all(target_os = "linux", not(feature = "mock")))]
+            Event::RedrawEventsCleared => {
+                use webview::platform::linux::WebViewExtLinux;
+
+                // GTK runs its own event queue, so poll it alongside winit's
+                web_view.pump_events();
+                *control_flow = ControlFlow::WaitUntil(Instant::now() + Duration::from_millis(16));
+            }
+            _ => (),
+        }
+    });
+}
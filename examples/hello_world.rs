@@ -1,7 +1,8 @@
 extern crate winit;
 extern crate winit_webview;
 
-use std::task::Waker;
+#[cfg(all(target_os = "linux", not(feature = "mock")))]
+use std::time::{Duration, Instant};
 
 use winit::{
     event::{Event, WindowEvent},
@@ -30,7 +31,7 @@ pub fn main() {
                 event: WindowEvent::CloseRequested,
                 window_id,
             } if window_id == web_view.window.id() => *control_flow = ControlFlow::Exit,
-            Event::UserEvent(webview::Event::Navigation(webview::NavigationEvent::Finish)) => {
+            Event::UserEvent(webview::Event::Navigation(webview::NavigationEvent::Finish(_))) => {
                 if let Some(title) = web_view.title() {
                     web_view.window.set_title(&title);
                 }
@@ -38,6 +39,14 @@ pub fn main() {
             Event::UserEvent(evt) => {
                 println!("{:?}", evt);
             }
+            #[cfg(all(target_os = "linux", not(feature = "mock")))]
+            Event::RedrawEventsCleared => {
+                use webview::platform::linux::WebViewExtLinux;
+
+                // GTK runs its own event queue, so poll it alongside winit's
+                web_view.pump_events();
+                *control_flow = ControlFlow::WaitUntil(Instant::now() + Duration::from_millis(16));
+            }
             _ => (),
         }
     });
@@ -1,8 +1,8 @@
-extern crate mime_guess;
 extern crate winit;
 extern crate winit_webview;
 
-use std::path::Path;
+#[cfg(all(target_os = "linux", not(feature = "mock")))]
+use std::time::{Duration, Instant};
 
 use winit::{
     event::{Event, WindowEvent},
@@ -11,30 +11,12 @@ use winit::{
     window::WindowBuilder,
 };
 use winit_webview as webview;
-
-struct RequestHandler;
-
-impl webview::request::RequestHandler for RequestHandler {
-    type Read = std::fs::File;
-
-    fn handle_request(&mut self, uri: &str) -> Option<webview::request::Response<Self::Read>> {
-        let path = Path::new("./examples/dist").join(Path::new(uri).strip_prefix("/").ok()?);
-
-        let file = std::fs::File::open(&path).ok()?;
-        if file.metadata().ok()?.is_file() {
-            Some(webview::request::Response {
-                body: file,
-                mime_type: mime_guess::from_ext(path.extension()?.to_str()?)
-                    .first_raw()?
-                    .to_owned(),
-            })
-        } else {
-            None
-        }
-    }
-}
+use webview::request::RequestHandler;
 
 pub fn main() {
+    #[cfg(all(target_os = "linux", not(feature = "mock")))]
+    use webview::platform::linux::WebViewBuilderExtLinux;
+    #[cfg(all(target_os = "macos", not(feature = "mock")))]
     use webview::platform::macos::WebViewBuilderExtMacOS;
 
     let mut event_loop = EventLoop::with_user_event();
@@ -44,9 +26,20 @@ pub fn main() {
         .build(&event_loop)
         .unwrap();
 
-    let mut web_view = webview::WebViewBuilder::with_request_handler(RequestHandler)
-        .with_debug(true)
-        .build(event_loop.create_proxy(), window);
+    let router = webview::request::Router::new()
+        .get("/api/greeting/:name", |request: webview::request::Request| {
+            let greeting = format!("Hello, {}!", request.param("name")?);
+            Some(webview::request::Response::bytes(greeting, "text/plain"))
+        })
+        .with_fallback(webview::request::StaticDir::new("./examples/dist"))
+        .with(webview::request::SecurityHeaders::new())
+        .with(webview::request::Logger::new());
+
+    let builder = webview::WebViewBuilder::with_request_handler(router);
+    #[cfg(not(feature = "mock"))]
+    let builder = builder.with_debug(true);
+
+    let mut web_view = builder.build(event_loop.create_proxy(), window);
     web_view.navigate(webview::NavigationTarget::Url("winit:///index.html"));
 
     event_loop.run_return(move |event, _, control_flow| {
@@ -57,7 +50,7 @@ pub fn main() {
                 event: WindowEvent::CloseRequested,
                 window_id,
             } if window_id == web_view.window.id() => *control_flow = ControlFlow::Exit,
-            Event::UserEvent(webview::Event::Navigation(webview::NavigationEvent::Finish)) => {
+            Event::UserEvent(webview::Event::Navigation(webview::NavigationEvent::Finish(_))) => {
                 if let Some(title) = web_view.title() {
                     web_view.window.set_title(&title);
                 }
@@ -65,6 +58,14 @@ pub fn main() {
             Event::UserEvent(evt) => {
                 println!("{:?}", evt);
             }
+            #[cfg(all(target_os = "linux", not(feature = "mock")))]
+            Event::RedrawEventsCleared => {
+                use webview::platform::linux::WebViewExtLinux;
+
+                // GTK runs its own event queue, so poll it alongside winit's
+                web_view.pump_events();
+                *control_flow = ControlFlow::WaitUntil(Instant::now() + Duration::from_millis(16));
+            }
             _ => (),
         }
     });
@@ -0,0 +1,113 @@
+extern crate mime_guess;
+extern crate proc_macro;
+
+use proc_macro::{TokenStream, TokenTree};
+use std::{
+    env,
+    fmt::Write,
+    fs,
+    path::{Path, PathBuf}
+};
+
+/// Embed a directory tree into the binary as a `winit_webview::request::EmbeddedDir`.
+///
+/// The path is relative to the directory containing the calling crate's `Cargo.toml`.
+/// MIME types and ETags are computed at compile time. Precompressed `.gz` or `.br` copies of
+/// other files (e.g. `app.js.br` next to `app.js`) are left out: WebKit doesn't decode
+/// `Content-Encoding` on custom schemes, so they could never be served.
+///
+/// Files are embedded with `include_bytes!`, so edits trigger a rebuild, but added or
+/// removed files are only picked up once the calling crate is rebuilt for another reason.
+#[proc_macro]
+pub fn include_assets(input: TokenStream) -> TokenStream {
+    let expanded = match expand(input) {
+        Ok(expanded) => expanded,
+        Err(message) => format!("compile_error!({:?})", message)
+    };
+
+    expanded.parse().unwrap()
+}
+
+fn expand(input: TokenStream) -> Result<String, String> {
+    let manifest_dir = env::var("CARGO_MANIFEST_DIR").map_err(|_| "CARGO_MANIFEST_DIR is not set".to_owned())?;
+    let root = Path::new(&manifest_dir).join(parse_path(input)?);
+    if !root.is_dir() {
+        return Err(format!("{} is not a directory", root.display()));
+    }
+
+    let mut files = Vec::new();
+    collect_files(&root, &root, &mut files)?;
+    files.sort();
+
+    let mut expanded = String::from("::winit_webview::request::EmbeddedDir::new(&[");
+    for (path, full_path) in &files {
+        if is_variant(&files, path) {
+            continue;
+        }
+
+        let contents = fs::read(full_path).map_err(|err| format!("Failed to read {}: {}", full_path.display(), err))?;
+
+        write!(
+            expanded,
+            "::winit_webview::request::EmbeddedFile {{ \
+                path: {:?}, \
+                contents: include_bytes!({:?}) as &[u8], \
+                mime_type: {:?}, \
+                etag: {:?} \
+            }},",
+            path,
+            full_path.display().to_string(),
+            mime_guess::from_path(path).first_raw().unwrap_or("application/octet-stream"),
+            format!("\"{:016x}\"", fnv1a(&contents))
+        )
+        .unwrap();
+    }
+    expanded.push_str("])");
+
+    Ok(expanded)
+}
+
+/// Read the single string literal passed to the macro
+fn parse_path(input: TokenStream) -> Result<String, String> {
+    let mut tokens = input.into_iter();
+    let literal = match (tokens.next(), tokens.next()) {
+        (Some(TokenTree::Literal(literal)), None) => literal.to_string(),
+        _ => return Err("Expected a directory path, e.g. include_assets!(\"dist\")".to_owned())
+    };
+
+    match literal.strip_prefix('"').and_then(|literal| literal.strip_suffix('"')) {
+        Some(path) if !path.contains('\\') => Ok(path.to_owned()),
+        _ => Err("Expected a plain string literal without escapes".to_owned())
+    }
+}
+
+/// Find every file below `dir`, with its `/`-separated path relative to `root`
+fn collect_files(root: &Path, dir: &Path, files: &mut Vec<(String, PathBuf)>) -> Result<(), String> {
+    let entries = fs::read_dir(dir).map_err(|err| format!("Failed to read {}: {}", dir.display(), err))?;
+    for entry in entries {
+        let path = entry.map_err(|err| err.to_string())?.path();
+        if path.is_dir() {
+            collect_files(root, &path, files)?;
+        } else {
+            let relative = path.strip_prefix(root).unwrap();
+            let components: Vec<_> = relative.components().map(|component| component.as_os_str().to_string_lossy()).collect();
+
+            files.push((components.join("/"), path));
+        }
+    }
+
+    Ok(())
+}
+
+/// Check whether a file is a precompressed variant of another embedded file
+fn is_variant(files: &[(String, PathBuf)], path: &str) -> bool {
+    [".gz", ".br"].iter().any(|extension| match path.strip_suffix(extension) {
+        Some(original) => files.iter().any(|(path, _)| path == original),
+        None => false
+    })
+}
+
+/// Hash file contents for use as an ETag
+fn fnv1a(bytes: &[u8]) -> u64 {
+    bytes.iter().fold(0xcbf2_9ce4_8422_2325, |hash, byte| (hash ^ *byte as u64).wrapping_mul(0x0100_0000_01b3))
+}
@@ -0,0 +1,182 @@
+use std::{
+    cell::RefCell,
+    collections::{hash_map::RandomState, HashMap},
+    hash::{BuildHasher, Hasher},
+    io,
+    rc::Rc
+};
+
+use crate::{
+    request::{self, Request, RequestHandler, Response},
+    Event, MessageSource
+};
+
+/// The scheme binary messages travel over, in both directions
+pub(crate) const SCHEME: &str = "winit-ipc";
+
+/// Defines `window.winit.postBinary` and `onBinary` at the start of every page
+#[cfg(not(feature = "mock"))]
+pub(crate) const SCRIPT: &str = r#"(function () {
+    var winit = window.winit = window.winit || {};
+    var listeners = [];
+    var delivered = Promise.resolve();
+
+    winit.postBinary = function (data) {
+        return fetch("winit-ipc://localhost/message", { method: "POST", body: data }).then(function (response) {
+            if (!response.ok) {
+                throw new Error("Failed to post binary message: " + response.status);
+            }
+        });
+    };
+
+    winit.onBinary = function (callback) {
+        listeners.push(callback);
+        return function () {
+            var index = listeners.indexOf(callback);
+            if (index >= 0) {
+                listeners.splice(index, 1);
+            }
+        };
+    };
+
+    winit.__binary = function (token) {
+        var bytes = fetch("winit-ipc://localhost/binary/" + token).then(function (response) {
+            return response.arrayBuffer();
+        });
+
+        // Fetches may finish in any order, but messages are delivered in the order they were sent
+        delivered = delivered.then(function () {
+            return bytes;
+        }).then(function (buffer) {
+            var data = new Uint8Array(buffer);
+            listeners.slice().forEach(function (callback) {
+                try {
+                    callback(data);
+                } catch (error) {
+                    setTimeout(function () { throw error; });
+                }
+            });
+        }, function (error) {
+            setTimeout(function () { throw error; });
+        });
+    };
+})();"#;
+
+/// Bytes sent with `WebView::send_binary`, held until the page fetches them
+#[derive(Clone, Default)]
+pub(crate) struct Outbox {
+    messages: Rc<RefCell<HashMap<String, Vec<u8>>>>
+}
+
+impl Outbox {
+    /// Hold a message, returning a script that makes the page fetch it
+    pub(crate) fn push(&self, bytes: Vec<u8>) -> String {
+        let token = new_token();
+        self.messages.borrow_mut().insert(token.clone(), bytes);
+        format!("window.winit.__binary(\"{}\");", token)
+    }
+
+    /// Drop the messages sent to a page that is being left, whether or not it fetched them
+    pub(crate) fn clear(&self) {
+        self.messages.borrow_mut().clear();
+    }
+
+    fn take(&self, token: &str) -> Option<Vec<u8>> {
+        self.messages.borrow_mut().remove(token)
+    }
+}
+
+/// Create a token that pages can't guess, so each can only fetch the messages sent to it, once
+fn new_token() -> String {
+    // Each `RandomState` keys SipHash differently, from keys seeded by the OS
+    (0..2)
+        .map(|_| {
+            let mut hasher = RandomState::new().build_hasher();
+            hasher.write_u64(crate::script::next_id());
+            format!("{:016x}", hasher.finish())
+        })
+        .collect()
+}
+
+/// Handles the `winit-ipc` scheme. Pages post messages to `/message`,
+/// and fetch the messages sent to them from `/binary/<token>`.
+///
+/// Only pages on the app's own schemes, or at the base URL's origin, are answered.
+/// Anything else, such as a third-party iframe or a site the view navigated to, is refused.
+pub(crate) struct BinaryHandler {
+    events: Rc<dyn Fn(Event)>,
+    outbox: Outbox,
+    schemes: Vec<String>,
+    base_origin: String
+}
+
+impl BinaryHandler {
+    /// Create a handler for pages on `winit`, the other registered schemes, or the base URL's origin
+    pub(crate) fn new<'a>(
+        events: Rc<dyn Fn(Event)>,
+        outbox: Outbox,
+        schemes: impl IntoIterator<Item = &'a String>,
+        base_url: &str
+    ) -> Self {
+        let mut schemes: Vec<String> = schemes.into_iter().cloned().collect();
+        schemes.push("winit".to_owned());
+
+        Self {
+            events,
+            outbox,
+            schemes,
+            base_origin: request::origin(base_url).to_ascii_lowercase()
+        }
+    }
+
+    fn allows(&self, origin: &str) -> bool {
+        let origin = origin.to_ascii_lowercase();
+        match origin.split_once("://") {
+            Some((scheme, _)) => origin == self.base_origin || self.schemes.iter().any(|name| name == scheme),
+            None => false
+        }
+    }
+}
+
+impl RequestHandler for BinaryHandler {
+    type Read = io::Cursor<Vec<u8>>;
+
+    fn handle(&mut self, request: Request) -> Option<Response<Self::Read>> {
+        // Cross-origin requests always carry an `Origin`, which pages can't forge
+        let origin = match request.header("Origin") {
+            Some(origin) if self.allows(origin) => origin.to_owned(),
+            _ => return Some(Response::empty(403).with_header("Cache-Control", "no-store"))
+        };
+
+        let response = match (request.method.as_str(), request.path().as_ref()) {
+            // Pages check they may post here first
+            ("OPTIONS", _) => Response::empty(204)
+                .with_header("Access-Control-Allow-Methods", "GET, POST")
+                .with_header("Access-Control-Allow-Headers", "Content-Type"),
+            ("POST", "/message") => {
+                let source = message_source(&request, &origin);
+                (self.events)(Event::BinaryMessage(request.body, Some(source)));
+                Response::empty(204)
+            }
+            ("GET", path) => Response::bytes(self.outbox.take(path.strip_prefix("/binary/")?)?, "application/octet-stream"),
+            _ => Response::empty(405)
+        };
+
+        let response = response
+            .with_header("Access-Control-Allow-Origin", origin)
+            .with_header("Vary", "Origin")
+            .with_header("Cache-Control", "no-store");
+        Some(response)
+    }
+}
+
+/// Tell where a binary message came from. The page's URL is only known from the `Referer`,
+/// which the page's referrer policy may cut down to its origin or leave out.
+fn message_source(request: &Request, origin: &str) -> MessageSource {
+    MessageSource {
+        url: request.header("Referer").unwrap_or(origin).to_owned(),
+        origin: origin.to_owned(),
+        main_frame: None,
+        frame_id: None
+    }
+}
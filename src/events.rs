@@ -8,26 +8,85 @@ pub use crate::platform_impl::PlatformNavigationEvent;
 pub enum Event {
     /// Navigation status has changed.
     Navigation(NavigationEvent),
-    /// A script has sent a message to the host webview.
-    Message(String),
+    /// A script has sent a message to the host webview, along with where it came from
+    /// if the engine reports it.
+    Message(String, Option<MessageSource>),
+    /// A script has sent a message to a channel registered with `WebViewBuilder::with_message_channel`.
+    ChannelMessage {
+        channel: String,
+        message: String,
+        source: Option<MessageSource>
+    },
+    /// A script has sent bytes with `window.winit.postBinary`, along with the origin of the page
+    /// that sent them.
+    BinaryMessage(Vec<u8>, Option<MessageSource>),
+    /// A script has sent a JSON value to the host webview, along with where it came from
+    /// if the engine reports it.
+    #[cfg(feature = "serde")]
+    Json(serde_json::Value, Option<MessageSource>),
+    /// A script has sent a message that couldn't be decoded.
+    #[cfg(feature = "serde")]
+    MessageError(crate::ipc::MessageError),
+    /// A script run with `WebView::evaluate_with_id` has completed.
+    ScriptResult {
+        id: u64,
+        result: crate::ScriptResult
+    },
     /// A platform-specific event has occurred.
     Platform(PlatformEvent)
 }
 
+/// The frame a message was sent from.
+///
+/// WKWebView reports this for every message. WebKitGTK doesn't tell the host which frame
+/// sent a message, so messages there have no source, except for binary messages, whose source
+/// comes from the headers of the request that carried them.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MessageSource {
+    /// The URL of the document that sent the message
+    pub url: String,
+    /// The origin of that document, e.g. `winit://app` or `https://example.com:8080`
+    pub origin: String,
+    /// Whether the message came from the top-level document, rather than an iframe,
+    /// or `None` if that isn't known, as for binary messages
+    pub main_frame: Option<bool>,
+    /// Identifies the frame within the view while it exists. WKWebView only reports this through
+    /// private API, so it is `None` unless the `macos-private-api` feature is enabled.
+    pub frame_id: Option<u64>
+}
+
 /// Navigation status has changed.
+///
+/// Only navigations of the main frame are reported. Each reports `Start`, `Commit` and `Finish`
+/// in turn, unless it fails, and carries the same id throughout.
 #[derive(Debug, Clone)]
 pub enum NavigationEvent {
     /// Triggered when navigation is initiated
-    Start,
+    Start(NavigationInfo),
     /// Triggered when navigation recieves content and begins loading it
-    Commit,
+    Commit(NavigationInfo),
     /// Triggered when navigation is complete
-    Finish,
+    Finish(NavigationInfo),
 
     /// Some other platform-specific navigation event
     Platform(PlatformNavigationEvent),
 }
 
+/// The navigation an event is about
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct NavigationInfo {
+    /// Identifies the navigation, the same for each of its events
+    pub id: u64,
+    /// The URL being loaded, which changes if the navigation is redirected
+    pub url: String,
+    /// Whether the navigation stayed within the current document, as with `history.pushState`
+    /// or a link to an anchor. These report all their events at once.
+    ///
+    /// WKWebView only reports these through private API, so on macOS they are only reported
+    /// with the `macos-private-api` feature.
+    pub same_document: bool
+}
+
 /// A recipient for WebView events.
 pub trait EventHandler: 'static + Sized {
     fn handle_event(&mut self, event: Event);
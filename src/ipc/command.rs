@@ -0,0 +1,156 @@
+use std::{collections::HashMap, fmt, marker::PhantomData};
+
+use serde::{de::DeserializeOwned, Serialize};
+use serde_json::Value;
+
+use crate::MessageSource;
+
+#[cfg(not(feature = "mock"))]
+use super::{js_literal, MessageError};
+
+type Completion = Box<dyn FnOnce(Result<Value, String>) + Send>;
+
+/// A type-erased command handler
+pub(crate) type BoxedCommand = Box<dyn FnMut(Value, Reply)>;
+
+/// The handler for each command, by name
+pub(crate) type Commands = HashMap<String, BoxedCommand>;
+
+/// Settles the promise returned by `window.winit.invoke`, either immediately or later from any thread.
+///
+/// Dropping the reply without answering rejects the promise.
+pub struct Reply<T: Serialize = Value> {
+    complete: Option<Completion>,
+    source: Option<MessageSource>,
+    result: PhantomData<fn(T)>
+}
+
+impl<T: Serialize> Reply<T> {
+    pub(crate) fn new(source: Option<MessageSource>, complete: impl FnOnce(Result<Value, String>) + Send + 'static) -> Self {
+        Self {
+            complete: Some(Box::new(complete)),
+            source,
+            result: PhantomData
+        }
+    }
+
+    /// The frame that made the call, if the engine reports it, so calls from embedded
+    /// third-party pages can be rejected
+    pub fn source(&self) -> Option<&MessageSource> {
+        self.source.as_ref()
+    }
+
+    /// Resolve the promise with a value
+    pub fn resolve(self, value: T) {
+        match serde_json::to_value(value) {
+            Ok(value) => self.complete(Ok(value)),
+            Err(err) => self.reject(format!("Failed to serialize the result: {}", err))
+        }
+    }
+
+    /// Reject the promise with an `Error` carrying the specified message
+    pub fn reject(self, error: impl fmt::Display) {
+        self.complete(Err(error.to_string()))
+    }
+
+    /// Resolve or reject the promise
+    pub fn respond<E: fmt::Display>(self, result: Result<T, E>) {
+        match result {
+            Ok(value) => self.resolve(value),
+            Err(err) => self.reject(err)
+        }
+    }
+
+    fn complete(mut self, result: Result<Value, String>) {
+        if let Some(complete) = self.complete.take() {
+            complete(result);
+        }
+    }
+
+    /// Change the type of the eventual result
+    fn cast<U: Serialize>(mut self) -> Reply<U> {
+        Reply {
+            complete: self.complete.take(),
+            source: self.source.take(),
+            result: PhantomData
+        }
+    }
+}
+
+impl<T: Serialize> Drop for Reply<T> {
+    fn drop(&mut self) {
+        if let Some(complete) = self.complete.take() {
+            complete(Err("The command didn't reply".to_owned()));
+        }
+    }
+}
+
+/// Erase the argument and result types of a command
+pub(crate) fn boxed<A, R>(name: String, mut handler: impl FnMut(A, Reply<R>) + 'static) -> BoxedCommand
+where
+    A: DeserializeOwned,
+    R: Serialize
+{
+    Box::new(move |args, reply: Reply| match A::deserialize(args) {
+        Ok(args) => handler(args, reply.cast()),
+        Err(err) => reply.reject(format!("Invalid arguments for `{}`: {}", name, err))
+    })
+}
+
+/// A call made with `window.winit.invoke`
+#[cfg(not(feature = "mock"))]
+#[derive(serde::Deserialize)]
+struct Call {
+    /// The page load the call was made from
+    page: String,
+    id: u64,
+    name: String,
+    #[serde(default)]
+    args: Value
+}
+
+/// Run the command a script has invoked. `send` is given a script that settles the call's promise,
+/// to run on the main thread, possibly from another thread.
+#[cfg(not(feature = "mock"))]
+pub(crate) fn dispatch(
+    commands: &mut Commands,
+    message: &str,
+    source: Option<MessageSource>,
+    send: impl FnOnce(String) + Send + 'static
+) -> Result<(), MessageError> {
+    let call: Call = serde_json::from_str(message).map_err(|err| MessageError {
+        message: err.to_string(),
+        body: Some(message.to_owned())
+    })?;
+
+    let (page, id) = (call.page, call.id);
+    call_command(commands, &call.name, call.args, source, move |result| send(reply_script(&page, id, result)));
+    Ok(())
+}
+
+/// Run a command, rejecting calls to unknown commands
+pub(crate) fn call_command(
+    commands: &mut Commands,
+    name: &str,
+    args: Value,
+    source: Option<MessageSource>,
+    complete: impl FnOnce(Result<Value, String>) + Send + 'static
+) {
+    let reply = Reply::new(source, complete);
+    match commands.get_mut(name) {
+        Some(command) => command(args, reply),
+        None => reply.reject(format!("Unknown command `{}`", name))
+    }
+}
+
+/// Create a script that settles the promise of a call
+#[cfg(not(feature = "mock"))]
+fn reply_script(page: &str, id: u64, result: Result<Value, String>) -> String {
+    let (error, value) = match result {
+        Ok(value) => (Value::Null, value),
+        Err(err) => (Value::String(err), Value::Null)
+    };
+
+    let page = Value::String(page.to_owned());
+    format!("window.winit.__reply({}, {}, {}, {});", js_literal(&page), id, js_literal(&error), js_literal(&value))
+}
@@ -0,0 +1,209 @@
+//! Typed JSON messages and commands from scripts.
+//!
+//! Pages send any JSON value with `window.winit.postMessage(value)`, which arrives as
+//! `Event::Json`. Values posted to the native message handler that aren't strings are
+//! converted to JSON too. Messages that can't be decoded arrive as `Event::MessageError`.
+//!
+//! Wrap an event handler in `Typed` to receive messages decoded into your own type:
+//!
+//! ```no_run
+//! # use winit_webview::ipc::{Typed, TypedEvent};
+//! #[derive(serde::Deserialize)]
+//! #[serde(tag = "type")]
+//! enum Message {
+//!     Save { path: String },
+//!     Quit
+//! }
+//!
+//! let handler = Typed::new(|event: TypedEvent<Message>| match event {
+//!     TypedEvent::Message(Message::Save { path }, _) => println!("Saving {}", path),
+//!     TypedEvent::Message(Message::Quit, _) => std::process::exit(0),
+//!     TypedEvent::Event(event) => println!("{:?}", event)
+//! });
+//! ```
+//!
+//! Scripts that need an answer call a command registered with `WebViewBuilder::with_command`
+//! instead. `window.winit.invoke(name, args)` returns a promise that resolves with the
+//! command's result, or rejects with its error. Commands registered with `with_async_command`
+//! can check where a call came from with `Reply::source`:
+//!
+//! ```no_run
+//! # use winit_webview::WebViewBuilder;
+//! let builder = WebViewBuilder::new()
+//!     .with_command("add", |(a, b): (i64, i64)| Ok::<_, String>(a + b));
+//!
+//! // In the page: `const sum = await window.winit.invoke("add", [1, 2]);`
+//! ```
+//!
+//! In the other direction, `WebView::emit(name, payload)` calls the listeners the page has
+//! registered with `window.winit.on(name, callback)`, which returns a function that removes
+//! the listener. Events emitted while a page is loading are delivered once it has loaded.
+
+use std::{error, fmt, marker::PhantomData};
+
+use serde::de::DeserializeOwned;
+
+use crate::{Event, EventHandler, MessageSource};
+
+mod command;
+pub use command::Reply;
+pub(crate) use command::{boxed, Commands};
+#[cfg(feature = "mock")]
+pub(crate) use command::call_command;
+#[cfg(not(feature = "mock"))]
+pub(crate) use command::dispatch;
+
+/// The name of the script message handler the bridge posts JSON text to
+#[cfg(not(feature = "mock"))]
+pub(crate) const JSON_HANDLER: &str = "WinitJsonHandler";
+
+/// The name of the script message handler the bridge posts command calls to
+#[cfg(not(feature = "mock"))]
+pub(crate) const INVOKE_HANDLER: &str = "WinitInvokeHandler";
+
+/// Defines `window.winit.postMessage`, `invoke` and `on` at the start of every top-level page
+#[cfg(not(feature = "mock"))]
+pub(crate) const BRIDGE_SCRIPT: &str = r#"(function () {
+    var winit = window.winit = window.winit || {};
+    var handlers = window.webkit.messageHandlers;
+    var calls = {};
+    var nextId = 1;
+    // Calls are tagged with the page that made them, so a reply that arrives after a navigation
+    // can't settle a call with the same id on the next page
+    var page = Math.random().toString(36).slice(2) + Date.now().toString(36);
+    var listeners = {};
+
+    winit.postMessage = function (value) {
+        var json = JSON.stringify(value === undefined ? null : value);
+        handlers.WinitJsonHandler.postMessage(json);
+    };
+
+    winit.invoke = function (name, args) {
+        return new Promise(function (resolve, reject) {
+            var id = nextId++;
+            var json = JSON.stringify({ page: page, id: id, name: String(name), args: args === undefined ? null : args });
+
+            calls[id] = { resolve: resolve, reject: reject };
+            handlers.WinitInvokeHandler.postMessage(json);
+        });
+    };
+
+    winit.__reply = function (to, id, error, result) {
+        var call = to === page && calls[id];
+        if (call) {
+            delete calls[id];
+            error === null ? call.resolve(result) : call.reject(new Error(error));
+        }
+    };
+
+    winit.on = function (name, callback) {
+        (listeners[name] = listeners[name] || []).push(callback);
+        return function () {
+            var list = listeners[name] || [];
+            var index = list.indexOf(callback);
+            if (index >= 0) {
+                list.splice(index, 1);
+            }
+        };
+    };
+
+    winit.__emit = function (name, payload) {
+        (listeners[name] || []).slice().forEach(function (callback) {
+            try {
+                callback(payload);
+            } catch (error) {
+                // Report the error without keeping the other listeners from running
+                setTimeout(function () { throw error; });
+            }
+        });
+    };
+})();"#;
+
+/// Serialize a value as a JavaScript expression
+pub(crate) fn js_literal(value: &serde_json::Value) -> String {
+    // JSON is valid JavaScript, except that older engines reject these line terminators in strings
+    value.to_string().replace('\u{2028}', "\\u2028").replace('\u{2029}', "\\u2029")
+}
+
+/// Create a script that calls the page's listeners for an event
+pub(crate) fn emit_script(name: &str, payload: &serde_json::Value) -> String {
+    let name = serde_json::Value::String(name.to_owned());
+    format!("window.winit.__emit({}, {});", js_literal(&name), js_literal(payload))
+}
+
+/// A message from a script that couldn't be decoded
+#[derive(Debug, Clone, PartialEq)]
+pub struct MessageError {
+    /// What went wrong
+    pub message: String,
+    /// The message as received, if it could be read as text
+    pub body: Option<String>
+}
+
+impl fmt::Display for MessageError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Failed to decode message: {}", self.message)
+    }
+}
+
+impl error::Error for MessageError {}
+
+/// Decode JSON text received from a script
+pub(crate) fn json_event(json: &str, source: Option<MessageSource>) -> Event {
+    match serde_json::from_str(json) {
+        Ok(value) => Event::Json(value, source),
+        Err(err) => Event::MessageError(MessageError {
+            message: err.to_string(),
+            body: Some(json.to_owned())
+        })
+    }
+}
+
+/// An event, with JSON messages decoded into `T`
+#[derive(Debug, Clone)]
+pub enum TypedEvent<T> {
+    /// A message, along with where it came from if the engine reports it
+    Message(T, Option<MessageSource>),
+    /// Any other event, including `Event::MessageError` for messages that aren't a valid `T`
+    Event(Event)
+}
+
+/// An event handler that decodes JSON messages into `T` before passing them on
+pub struct Typed<T, H> {
+    handler: H,
+    message: PhantomData<fn() -> T>
+}
+
+impl<T, H> Typed<T, H>
+where
+    T: DeserializeOwned + 'static,
+    H: FnMut(TypedEvent<T>) + 'static
+{
+    pub fn new(handler: H) -> Self {
+        Self {
+            handler,
+            message: PhantomData
+        }
+    }
+}
+
+impl<T, H> EventHandler for Typed<T, H>
+where
+    T: DeserializeOwned + 'static,
+    H: FnMut(TypedEvent<T>) + 'static
+{
+    fn handle_event(&mut self, event: Event) {
+        let event = match event {
+            Event::Json(value, source) => match T::deserialize(&value) {
+                Ok(message) => TypedEvent::Message(message, source),
+                Err(err) => TypedEvent::Event(Event::MessageError(MessageError {
+                    message: err.to_string(),
+                    body: Some(value.to_string())
+                }))
+            },
+            event => TypedEvent::Event(event)
+        };
+
+        (self.handler)(event)
+    }
+}
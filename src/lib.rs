@@ -3,57 +3,192 @@ extern crate core_graphics;
 #[cfg(target_os = "macos")]
 extern crate objc_foundation;
 #[cfg(target_os = "macos")]
-#[macro_use]
+#[cfg_attr(not(feature = "mock"), macro_use)]
 extern crate objc;
 #[cfg(target_os = "macos")]
 extern crate block;
+#[cfg(all(target_os = "linux", feature = "webkitgtk"))]
+extern crate gio;
+#[cfg(all(target_os = "linux", feature = "webkitgtk"))]
+extern crate glib;
+#[cfg(all(target_os = "linux", feature = "webkitgtk"))]
+extern crate gtk;
+#[cfg(all(target_os = "linux", feature = "webkitgtk"))]
+extern crate javascriptcore;
+#[cfg(all(target_os = "linux", feature = "webkitgtk"))]
+extern crate soup;
+#[cfg(all(target_os = "linux", feature = "webkitgtk"))]
+extern crate webkit2gtk;
 
-
+#[cfg(all(target_os = "macos", not(feature = "mock")))]
 #[macro_use]
 extern crate paste;
+extern crate mime_guess;
+#[cfg(feature = "archive")]
+extern crate flate2;
+#[cfg(feature = "archive")]
+extern crate tar;
+#[cfg(feature = "archive")]
+extern crate zip;
+#[cfg(feature = "serde")]
+extern crate serde;
+#[cfg(feature = "serde")]
+extern crate serde_json;
 extern crate winit;
+extern crate winit_webview_macros;
+
+use std::collections::HashMap;
 
 mod events;
 pub use events::*;
 
+mod script;
+pub use script::{ScriptError, ScriptFuture, ScriptResult};
+
+mod binary;
+
+mod navigation;
+pub use navigation::{NavigationAction, NavigationPolicy, NavigationType};
+
+pub use winit_webview_macros::include_assets;
+
 pub mod request;
 
+#[cfg(feature = "serde")]
+pub mod ipc;
+
 pub mod platform;
 mod platform_impl;
 
+/// The default base URL for `NavigationTarget::Html`
+const DEFAULT_BASE_URL: &str = "winit://";
+
 /// A builder for the WebView component.
 pub struct WebViewBuilder<T: request::RequestHandler> {
+    /// The handler for the `winit` scheme
     pub(crate) request_handler: T,
+    /// Handlers for any other custom schemes, by scheme
+    pub(crate) schemes: HashMap<String, request::BoxedHandler>,
+    pub(crate) base_url: String,
     pub(crate) init_scripts: Vec<String>,
+    /// Names of the message handlers registered besides `WinitMessageHandler`
+    pub(crate) channels: Vec<String>,
+    /// Decides whether navigations go ahead
+    pub(crate) navigation_policy: Option<navigation::PolicyHandler>,
+    /// Handlers for `window.winit.invoke`, by command
+    #[cfg(feature = "serde")]
+    pub(crate) commands: ipc::Commands,
     pub(crate) platform: platform_impl::PlatformWebViewBuilder
 }
 
 impl WebViewBuilder<request::NullRequestHandler> {
     /// Create a new WebViewBuilder
     pub fn new() -> Self {
-        Self {
-            init_scripts: Vec::new(),
-            platform: platform_impl::PlatformWebViewBuilder::new(),
-            request_handler: request::NullRequestHandler
-        }
+        Self::with_request_handler(request::NullRequestHandler)
+    }
+}
+
+impl Default for WebViewBuilder<request::NullRequestHandler> {
+    fn default() -> Self {
+        Self::new()
     }
 }
 
 impl<T: request::RequestHandler> WebViewBuilder<T> {
     pub fn with_request_handler(request_handler: T) -> Self {
         Self {
+            request_handler,
+            schemes: HashMap::new(),
+            base_url: DEFAULT_BASE_URL.to_owned(),
             init_scripts: Vec::new(),
-            platform: platform_impl::PlatformWebViewBuilder::new(),
-            request_handler
+            channels: Vec::new(),
+            navigation_policy: None,
+            #[cfg(feature = "serde")]
+            commands: HashMap::new(),
+            platform: platform_impl::PlatformWebViewBuilder::new()
         }
     }
 
+    /// Handle requests to another custom scheme, e.g. `app` for `app://` URLs.
+    ///
+    /// Registering `winit` replaces the builder's request handler. Panics for schemes the engine
+    /// handles itself, such as `http` or `file`, and for `winit-ipc`, which is reserved for binary messages.
+    pub fn with_scheme(mut self, scheme: impl AsRef<str>, handler: impl request::RequestHandler) -> Self {
+        let scheme = scheme.as_ref().to_ascii_lowercase();
+
+        let valid = scheme.starts_with(|c: char| c.is_ascii_alphabetic())
+            && scheme.chars().all(|c| c.is_ascii_alphanumeric() || matches!(c, '+' | '-' | '.'));
+        assert!(valid, "`{}` isn't a valid scheme name", scheme);
+        assert!(!platform_impl::handles_scheme(&scheme), "The `{}` scheme is handled by the webview itself", scheme);
+        assert!(scheme != binary::SCHEME, "The `{}` scheme is reserved", scheme);
+
+        self.schemes.insert(scheme, request::boxed(handler));
+        self
+    }
+
+    /// Set the base URL that relative URLs resolve against in `NavigationTarget::Html` pages.
+    /// Defaults to `winit://`.
+    pub fn with_base_url(mut self, base_url: impl Into<String>) -> Self {
+        self.base_url = base_url.into();
+        self
+    }
+
     /// Add a script to be injected into the page when it loads
     pub fn with_init_script(mut self, script: impl AsRef<str>) -> Self {
         self.init_scripts.push(script.as_ref().to_owned());
         self
     }
 
+    /// Register another message handler, which scripts post strings to with
+    /// `window.webkit.messageHandlers[name].postMessage(text)`. Its messages arrive as
+    /// `Event::ChannelMessage` tagged with the name.
+    ///
+    /// Names starting with `Winit` are reserved for the handlers this crate registers.
+    pub fn with_message_channel(mut self, name: impl Into<String>) -> Self {
+        let name = name.into();
+        assert!(!name.starts_with("Winit"), "The message channel name `{}` is reserved", name);
+
+        if !self.channels.contains(&name) {
+            self.channels.push(name);
+        }
+        self
+    }
+
+    /// Decide whether each navigation goes ahead, e.g. to keep the view on the app's own origin.
+    ///
+    /// The policy sees navigations started by the page, such as link clicks, form submissions
+    /// and links that open new windows, as well as those started with `WebView::navigate`.
+    /// Without a policy, every navigation is allowed.
+    pub fn with_navigation_policy(mut self, policy: impl FnMut(&NavigationAction) -> NavigationPolicy + 'static) -> Self {
+        self.navigation_policy = Some(Box::new(policy));
+        self
+    }
+
+    /// Answer `window.winit.invoke(name, args)` calls from scripts. The arguments are decoded from JSON,
+    /// and the promise resolves with the handler's result or rejects with its error.
+    #[cfg(feature = "serde")]
+    pub fn with_command<A, R, E>(self, name: impl Into<String>, mut handler: impl FnMut(A) -> Result<R, E> + 'static) -> Self
+    where
+        A: serde::de::DeserializeOwned,
+        R: serde::Serialize,
+        E: std::fmt::Display
+    {
+        self.with_async_command(name, move |args, reply: ipc::Reply<R>| reply.respond(handler(args)))
+    }
+
+    /// Answer `window.winit.invoke(name, args)` calls through an `ipc::Reply`, which can be sent to
+    /// another thread to settle the promise once the work is done
+    #[cfg(feature = "serde")]
+    pub fn with_async_command<A, R>(mut self, name: impl Into<String>, handler: impl FnMut(A, ipc::Reply<R>) + 'static) -> Self
+    where
+        A: serde::de::DeserializeOwned,
+        R: serde::Serialize
+    {
+        let name = name.into();
+        self.commands.insert(name.clone(), ipc::boxed(name, handler));
+        self
+    }
+
     /// Construct the WebView component
     pub fn build(self, event_handler: impl EventHandler, mut window: winit::window::Window) -> WebView {
         WebView {
@@ -64,9 +199,12 @@ impl<T: request::RequestHandler> WebViewBuilder<T> {
 }
 
 /// A handle to the WebView component
+///
+/// `W` is the window hosting the view. Headless views (such as those built by the `mock` backend)
+/// have no window and use `()`.
 /// TODO: Upon dropping this handle, detach the webview from the winit window
-pub struct WebView {
-    pub window: winit::window::Window,
+pub struct WebView<W = winit::window::Window> {
+    pub window: W,
     platform: platform_impl::PlatformWebView
 }
 
@@ -76,7 +214,7 @@ pub enum NavigationTarget<'a> {
     Html(&'a str)
 }
 
-impl WebView {
+impl<W> WebView<W> {
     pub fn navigate(&mut self, target: NavigationTarget) {
         self.platform.navigate(target)
     }
@@ -85,6 +223,49 @@ impl WebView {
         self.platform.execute(js)
     }
 
+    /// Call the listeners the page has registered for an event with `window.winit.on(name, callback)`,
+    /// passing them the payload serialized as JSON.
+    ///
+    /// Events emitted while a page is loading are delivered once it has loaded.
+    #[cfg(feature = "serde")]
+    pub fn emit(&mut self, name: &str, payload: impl serde::Serialize) -> Result<(), serde_json::Error> {
+        let payload = serde_json::to_value(payload)?;
+        self.platform.emit(ipc::emit_script(name, &payload));
+        Ok(())
+    }
+
+    /// Send bytes to the listeners the page has registered with `window.winit.onBinary(callback)`,
+    /// which receive them as a `Uint8Array`.
+    ///
+    /// The page fetches the bytes over the `winit-ipc` scheme, so they aren't encoded into a script,
+    /// with a token that's only good once. Messages sent while a page is loading are delivered once
+    /// it has loaded, and those a page never fetches are dropped when the view navigates away from it.
+    pub fn send_binary(&mut self, bytes: impl Into<Vec<u8>>) {
+        let script = self.platform.outbox.push(bytes.into());
+        self.platform.emit(script);
+    }
+
+    /// Run a script and pass its result to `callback`, which is called on the main thread
+    pub fn evaluate_with_result(&mut self, js: impl AsRef<str>, callback: impl FnOnce(ScriptResult) + 'static) {
+        self.platform.evaluate_with_result(js, Box::new(callback))
+    }
+
+    /// Run a script, returning a future of its result
+    pub fn evaluate_async(&mut self, js: impl AsRef<str>) -> ScriptFuture {
+        let (future, complete) = ScriptFuture::new();
+        self.platform.evaluate_with_result(js, complete);
+        future
+    }
+
+    /// Run a script, delivering its result to the event handler as `Event::ScriptResult`
+    /// with the returned id
+    pub fn evaluate_with_id(&mut self, js: impl AsRef<str>) -> u64 {
+        let id = script::next_id();
+        let events = self.platform.event_sink();
+        self.platform.evaluate_with_result(js, Box::new(move |result| events(Event::ScriptResult { id, result })));
+        id
+    }
+
     pub fn title(&self) -> Option<String> {
         self.platform.title()
     }
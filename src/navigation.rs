@@ -0,0 +1,46 @@
+use std::sync::atomic::{AtomicU64, Ordering};
+
+/// A navigation the page or the host is about to make, passed to the policy set with
+/// `WebViewBuilder::with_navigation_policy`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct NavigationAction {
+    /// The URL being navigated to
+    pub url: String,
+    /// What started the navigation
+    pub navigation_type: NavigationType,
+    /// Whether the navigation loads a top-level document, rather than an iframe's.
+    ///
+    /// WebKitGTK doesn't report which frame a navigation targets, so this is `None` there,
+    /// except for navigations that open a new window.
+    pub main_frame: Option<bool>
+}
+
+/// What started a navigation
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum NavigationType {
+    LinkClicked,
+    FormSubmitted,
+    BackForward,
+    Reload,
+    FormResubmitted,
+    /// Anything else, including `WebView::navigate` and scripts setting `location`
+    Other
+}
+
+/// Whether a navigation goes ahead
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum NavigationPolicy {
+    Allow,
+    Cancel,
+    /// Cancel the navigation and open the URL in the default application for it, e.g. the browser
+    OpenExternally
+}
+
+/// A type-erased navigation policy
+pub(crate) type PolicyHandler = Box<dyn FnMut(&NavigationAction) -> NavigationPolicy>;
+
+/// Get a new id for a navigation, unique across views
+pub(crate) fn next_id() -> u64 {
+    static NEXT_ID: AtomicU64 = AtomicU64::new(1);
+    NEXT_ID.fetch_add(1, Ordering::Relaxed)
+}
@@ -0,0 +1,41 @@
+use crate::*;
+
+pub trait WebViewExtLinux {
+    /// Get the internal WebKitGTK view
+    fn webkit_web_view(&self) -> webkit2gtk::WebView;
+
+    /// Process pending GTK events and resize the view to fit its window.
+    ///
+    /// GTK is not driven by the winit event loop, so this should be called regularly,
+    /// e.g. on every `MainEventsCleared`.
+    fn pump_events(&mut self);
+}
+
+impl WebViewExtLinux for WebView {
+    fn webkit_web_view(&self) -> webkit2gtk::WebView {
+        self.platform.web_view.clone()
+    }
+
+    fn pump_events(&mut self) {
+        self.platform.pump_events(&self.window)
+    }
+}
+
+pub trait WebViewBuilderExtLinux {
+    fn with_debug(self, enable: bool) -> Self;
+
+    /// Allow WebKit to use GPU compositing. Disable this to render in software, e.g. under Xvfb.
+    fn with_hardware_acceleration(self, enable: bool) -> Self;
+}
+
+impl<T: request::RequestHandler> WebViewBuilderExtLinux for WebViewBuilder<T> {
+    fn with_debug(mut self, enable: bool) -> Self {
+        self.platform.enable_debug = enable;
+        self
+    }
+
+    fn with_hardware_acceleration(mut self, enable: bool) -> Self {
+        self.platform.hardware_acceleration = enable;
+        self
+    }
+}
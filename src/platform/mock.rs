@@ -0,0 +1,202 @@
+use crate::*;
+
+/// A navigation recorded by the mock backend
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Navigation {
+    Url(String),
+    Html(String)
+}
+
+pub trait WebViewExtMock {
+    /// Get every navigation requested so far, oldest first
+    fn navigations(&self) -> &[Navigation];
+
+    /// Get every script passed to `WebView::evaluate` so far, oldest first
+    fn evaluated_scripts(&self) -> &[String];
+
+    /// Get the init scripts the view was built with
+    fn init_scripts(&self) -> &[String];
+
+    /// Get the base URL that `NavigationTarget::Html` pages are loaded with
+    fn base_url(&self) -> &str;
+
+    /// Set the title reported by `WebView::title`
+    fn set_title(&mut self, title: Option<String>);
+
+    /// Deliver an event to the view's event handler
+    fn inject_event(&mut self, event: Event);
+
+    /// Deliver the Start event of a navigation to the URL most recently passed to `WebView::navigate`,
+    /// as the engine does once it starts loading. Events emitted from then on are held until the load ends.
+    fn start_navigation(&mut self);
+
+    /// Deliver the Commit and Finish events of the navigation started with `start_navigation`,
+    /// or the Start, Commit and Finish events of a successful navigation to the URL most recently
+    /// passed to `WebView::navigate`
+    fn complete_navigation(&mut self);
+
+    /// End the navigation started with `start_navigation` without finishing it, as when it fails
+    fn fail_navigation(&mut self);
+
+    /// Deliver the events of a navigation within the document, as `history.pushState` would cause
+    fn navigate_within_document(&mut self, url: &str);
+
+    /// Complete the oldest script run with `evaluate_with_result`, `evaluate_async` or `evaluate_with_id`
+    /// that hasn't completed yet. Returns `false` if no script was waiting.
+    fn complete_script(&mut self, result: ScriptResult) -> bool;
+
+    /// Ask the policy set with `WebViewBuilder::with_navigation_policy` about a navigation,
+    /// as the engine would before making it
+    fn decide_policy(&mut self, action: &NavigationAction) -> NavigationPolicy;
+
+    /// Post a message to a channel registered with `WebViewBuilder::with_message_channel`.
+    /// Returns `false` if the channel isn't registered, so the page would find no handler.
+    fn post_to_channel(&mut self, channel: &str, message: &str) -> bool;
+
+    /// Post bytes over the `winit-ipc` scheme, as `window.winit.postBinary` would
+    /// from the page most recently passed to `WebView::navigate`
+    fn post_binary(&mut self, bytes: &[u8]);
+
+    /// Deliver JSON text, as `window.winit.postMessage` would send it
+    #[cfg(feature = "serde")]
+    fn post_json(&mut self, json: &str);
+
+    /// Call a command, as `window.winit.invoke` would, and wait for it to reply.
+    /// Rejections are returned as errors.
+    #[cfg(feature = "serde")]
+    fn invoke(&mut self, name: &str, args: serde_json::Value) -> Result<serde_json::Value, String>;
+
+    /// Issue a `GET` request, as the page would for a `winit://` URL or one of another registered scheme.
+    ///
+    /// Blocks until the handler responds, which asynchronous handlers may do from another thread.
+    fn request(&mut self, url: &str) -> Option<request::Response<request::BoxedBody>>;
+
+    /// Issue an arbitrary request against the handler for its URL's scheme
+    fn send_request(&mut self, request: request::Request) -> Option<request::Response<request::BoxedBody>>;
+}
+
+impl<W> WebViewExtMock for WebView<W> {
+    fn navigations(&self) -> &[Navigation] {
+        &self.platform.navigations
+    }
+
+    fn evaluated_scripts(&self) -> &[String] {
+        &self.platform.evaluated_scripts
+    }
+
+    fn init_scripts(&self) -> &[String] {
+        &self.platform.init_scripts
+    }
+
+    fn base_url(&self) -> &str {
+        &self.platform.base_url
+    }
+
+    fn set_title(&mut self, title: Option<String>) {
+        self.platform.title = title;
+    }
+
+    fn inject_event(&mut self, event: Event) {
+        self.platform.dispatch(event)
+    }
+
+    fn start_navigation(&mut self) {
+        let navigation = next_navigation(&self.platform);
+        self.platform.start_navigation(navigation);
+    }
+
+    fn complete_navigation(&mut self) {
+        let navigation = next_navigation(&self.platform);
+        self.platform.complete_navigation(navigation);
+    }
+
+    fn fail_navigation(&mut self) {
+        self.platform.fail_navigation();
+    }
+
+    fn navigate_within_document(&mut self, url: &str) {
+        self.platform.navigate_within_document(NavigationInfo {
+            id: navigation::next_id(),
+            url: url.to_owned(),
+            same_document: true
+        });
+    }
+
+    fn complete_script(&mut self, result: ScriptResult) -> bool {
+        self.platform.complete_script(result)
+    }
+
+    fn decide_policy(&mut self, action: &NavigationAction) -> NavigationPolicy {
+        self.platform.decide_policy(action)
+    }
+
+    fn post_to_channel(&mut self, channel: &str, message: &str) -> bool {
+        self.platform.post_to_channel(channel, message)
+    }
+
+    fn post_binary(&mut self, bytes: &[u8]) {
+        let url = format!("{}://localhost/message", binary::SCHEME);
+        let page = current_url(&self.platform);
+        let origin = request::origin(&page).to_owned();
+        let request = request::Request::new("POST", url).with_header("Origin", origin).with_header("Referer", page);
+        self.platform.request(request.with_body(bytes));
+    }
+
+    #[cfg(feature = "serde")]
+    fn post_json(&mut self, json: &str) {
+        self.platform.dispatch(ipc::json_event(json, None))
+    }
+
+    #[cfg(feature = "serde")]
+    fn invoke(&mut self, name: &str, args: serde_json::Value) -> Result<serde_json::Value, String> {
+        self.platform.invoke(name, args)
+    }
+
+    fn request(&mut self, url: &str) -> Option<request::Response<request::BoxedBody>> {
+        self.platform.request(request::Request::get(url))
+    }
+
+    fn send_request(&mut self, request: request::Request) -> Option<request::Response<request::BoxedBody>> {
+        self.platform.request(request)
+    }
+}
+
+/// Get the URL of the page most recently passed to `WebView::navigate`
+fn current_url(platform: &platform_impl::PlatformWebView) -> String {
+    match platform.navigations.last() {
+        Some(Navigation::Url(url)) => url.clone(),
+        Some(Navigation::Html(_)) => platform.base_url.clone(),
+        None => "about:blank".to_owned()
+    }
+}
+
+/// Describe a new navigation to the URL most recently passed to `WebView::navigate`
+fn next_navigation(platform: &platform_impl::PlatformWebView) -> NavigationInfo {
+    NavigationInfo {
+        id: navigation::next_id(),
+        url: current_url(platform),
+        same_document: false
+    }
+}
+
+pub trait WebViewBuilderExtMock {
+    /// Set the title the view reports before any is set with `WebViewExtMock::set_title`
+    fn with_title(self, title: impl Into<String>) -> Self;
+
+    /// Construct a WebView that isn't attached to any window
+    fn build_headless(self, event_handler: impl EventHandler) -> WebView<()>;
+}
+
+impl<T: request::RequestHandler> WebViewBuilderExtMock for WebViewBuilder<T> {
+    fn with_title(mut self, title: impl Into<String>) -> Self {
+        self.platform.title = Some(title.into());
+        self
+    }
+
+    fn build_headless(self, event_handler: impl EventHandler) -> WebView<()> {
+        WebView {
+            platform: platform_impl::PlatformWebView::build_headless(self, event_handler),
+            window: (),
+        }
+    }
+}
@@ -1,2 +1,8 @@
-#[cfg(target_os = "macos")]
+#[cfg(all(target_os = "macos", not(feature = "mock")))]
 pub mod macos;
+
+#[cfg(all(target_os = "linux", not(feature = "mock")))]
+pub mod linux;
+
+#[cfg(feature = "mock")]
+pub mod mock;
@@ -0,0 +1,662 @@
+use crate::*;
+#[cfg(feature = "v2_40")]
+use glib::translate::{from_glib_full, ToGlibPtr};
+use gtk::prelude::*;
+use javascriptcore::ValueExt;
+use std::{cell::RefCell, io, os::unix::net::UnixStream, rc::Rc, thread};
+use webkit2gtk::{
+    HardwareAccelerationPolicy, LoadEvent, NavigationPolicyDecision, NavigationPolicyDecisionExt, PolicyDecisionExt,
+    PolicyDecisionType, SecurityManagerExt, SettingsExt, URIRequestExt,
+    URISchemeRequest, URISchemeRequestExt, URISchemeResponse, URISchemeResponseExt, UserContentInjectedFrames, UserContentManager, UserContentManagerExt,
+    UserScript, UserScriptInjectionTime, WebContext, WebContextExt, WebViewExt,
+};
+use winit::{platform::unix::WindowExtUnix, window::Window};
+
+#[derive(Debug)]
+pub struct PlatformWebViewBuilder {
+    pub(crate) enable_debug: bool,
+    pub(crate) hardware_acceleration: bool,
+}
+
+impl PlatformWebViewBuilder {
+    pub fn new() -> Self {
+        Default::default()
+    }
+}
+
+impl Default for PlatformWebViewBuilder {
+    fn default() -> Self {
+        Self {
+            enable_debug: false,
+            hardware_acceleration: true,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub enum PlatformNavigationEvent {
+    /// Triggered when a redirect has taken place, with the URL redirected to
+    Redirect(NavigationInfo),
+}
+
+#[derive(Debug, Clone, Copy)]
+pub enum PlatformEvent {}
+
+pub struct PlatformWebView {
+    pub(crate) plug: gtk::Plug,
+    pub(crate) web_view: webkit2gtk::WebView,
+    events: Rc<dyn Fn(Event)>,
+    /// Scripts emitting events, held back while the page loads
+    event_queue: Rc<RefCell<script::EventQueue>>,
+    pub(crate) outbox: binary::Outbox,
+    base_url: String,
+    size: (i32, i32),
+}
+
+/// The main frame's current load, used to give its events an id and to spot navigations within the document
+#[derive(Default)]
+struct NavigationState {
+    id: u64,
+    /// Whether the load hasn't been committed yet, so URL changes belong to it
+    provisional: bool,
+    /// Whether the load failed, so it doesn't finish
+    failed: bool,
+    url: String,
+}
+
+#[cfg(feature = "v2_40")]
+extern "C" {
+    // Added in WebKitGTK 2.40, and not yet bound by webkit2gtk-rs
+    fn webkit_uri_scheme_request_get_http_body(
+        request: *mut webkit2gtk::ffi::WebKitURISchemeRequest,
+    ) -> *mut gio::ffi::GInputStream;
+}
+
+impl PlatformWebView {
+    pub fn build(
+        builder: WebViewBuilder<impl request::RequestHandler>,
+        event_handler: impl EventHandler,
+        window: &mut Window,
+    ) -> Self {
+        let WebViewBuilder {
+            init_scripts,
+            channels,
+            navigation_policy,
+            request_handler,
+            mut schemes,
+            base_url,
+            #[cfg(feature = "serde")]
+            commands,
+            platform,
+        } = builder;
+
+        if !gtk::is_initialized_main_thread() {
+            // WebKitGTK is embedded through XEmbed, so GDK must talk to the same X server as winit
+            gtk::gdk::set_allowed_backends("x11");
+            gtk::init().expect("Failed to initialize GTK");
+        }
+
+        let xid = window
+            .xlib_window()
+            .expect("The WebKitGTK backend requires an X11 window");
+
+        let event_handler = Rc::new(RefCell::new(event_handler));
+        let events: Rc<dyn Fn(Event)> = {
+            let event_handler = event_handler.clone();
+            Rc::new(move |event| event_handler.borrow_mut().handle_event(event))
+        };
+
+        // Binary messages travel over their own scheme, in both directions
+        let outbox = binary::Outbox::default();
+        let binary_handler = binary::BinaryHandler::new(events.clone(), outbox.clone(), schemes.keys(), &base_url);
+        schemes.insert(binary::SCHEME.to_owned(), request::boxed(binary_handler));
+
+        let context = WebContext::new();
+        let security_manager = context.security_manager();
+
+        // Route each custom scheme to its request handler
+        for (scheme, request_handler) in request::scheme_handlers(request_handler, schemes) {
+            let request_handler = Rc::new(RefCell::new(request_handler));
+            context.register_uri_scheme(&scheme, move |request| {
+                let request_handler = request_handler.clone();
+                let scheme_request = request.clone();
+
+                // The handler gets the whole body, read without blocking the main loop
+                read_body(request, move |body| match body {
+                    Ok(body) => {
+                        let converted = convert_request(&scheme_request).with_body(body);
+                        handle_request(scheme_request, converted, &mut request_handler.borrow_mut());
+                    }
+                    Err(mut error) => scheme_request.finish_error(&mut error),
+                });
+            });
+
+            if let Some(security_manager) = &security_manager {
+                security_manager.register_uri_scheme_as_secure(&scheme);
+                security_manager.register_uri_scheme_as_cors_enabled(&scheme);
+            }
+        }
+
+        let manager = UserContentManager::new();
+
+        // Register a custom message handler
+        {
+            let event_handler = event_handler.clone();
+            manager.connect_script_message_received(Some("WinitMessageHandler"), move |_, result| {
+                if let Some(value) = result.js_value() {
+                    if value.is_string() {
+                        event_handler.borrow_mut().handle_event(Event::Message(value.to_str().to_string(), None));
+                    } else {
+                        #[cfg(feature = "serde")]
+                        event_handler.borrow_mut().handle_event(json_message(&value));
+                    }
+                }
+            });
+        }
+        manager.register_script_message_handler("WinitMessageHandler");
+
+        // Register the named channels, whose messages are tagged with their name
+        for channel in channels {
+            let event_handler = event_handler.clone();
+            let name = channel.clone();
+            manager.connect_script_message_received(Some(&channel), move |_, result| {
+                if let Some(value) = result.js_value().filter(|value| value.is_string()) {
+                    event_handler.borrow_mut().handle_event(Event::ChannelMessage {
+                        channel: name.clone(),
+                        message: value.to_str().to_string(),
+                        source: None,
+                    });
+                }
+            });
+            manager.register_script_message_handler(&channel);
+        }
+
+        // Register the handler behind `window.winit.postMessage`
+        #[cfg(feature = "serde")]
+        {
+            let event_handler = event_handler.clone();
+            manager.connect_script_message_received(Some(ipc::JSON_HANDLER), move |_, result| {
+                if let Some(value) = result.js_value() {
+                    event_handler.borrow_mut().handle_event(ipc::json_event(&value.to_str(), None));
+                }
+            });
+            manager.register_script_message_handler(ipc::JSON_HANDLER);
+        }
+
+        // Run commands called with `window.winit.invoke`. Replies may come from other threads,
+        // so their scripts are run from the main loop once the view exists.
+        #[cfg(feature = "serde")]
+        let (reply_sender, reply_receiver) = glib::MainContext::channel::<String>(glib::PRIORITY_DEFAULT);
+        #[cfg(feature = "serde")]
+        {
+            let event_handler = event_handler.clone();
+            let commands = RefCell::new(commands);
+            manager.connect_script_message_received(Some(ipc::INVOKE_HANDLER), move |_, result| {
+                if let Some(value) = result.js_value() {
+                    let reply_sender = reply_sender.clone();
+                    let send = move |script| {
+                        reply_sender.send(script).ok();
+                    };
+
+                    if let Err(err) = ipc::dispatch(&mut commands.borrow_mut(), &value.to_str(), None, send) {
+                        event_handler.borrow_mut().handle_event(Event::MessageError(err));
+                    }
+                }
+            });
+            manager.register_script_message_handler(ipc::INVOKE_HANDLER);
+        }
+
+        // The JSON and binary bridges run before the page's own init scripts. Replies are only
+        // evaluated in the top frame, so iframes don't get `window.winit`, whose calls would never settle.
+        let init_scripts = init_scripts.into_iter().map(|script| (script, UserContentInjectedFrames::AllFrames));
+        #[cfg(feature = "serde")]
+        let init_scripts = std::iter::once((ipc::BRIDGE_SCRIPT.to_owned(), UserContentInjectedFrames::TopFrame)).chain(init_scripts);
+        let init_scripts = std::iter::once((binary::SCRIPT.to_owned(), UserContentInjectedFrames::AllFrames)).chain(init_scripts);
+
+        // Register all init scripts
+        for (script, frames) in init_scripts {
+            let script = UserScript::new(
+                script.as_str(),
+                frames,
+                UserScriptInjectionTime::Start,
+                &[],
+                &[],
+            );
+
+            manager.add_script(&script);
+        }
+
+        let web_view = webkit2gtk::WebView::builder()
+            .web_context(&context)
+            .user_content_manager(&manager)
+            .build();
+
+        if let Some(settings) = WebViewExt::settings(&web_view) {
+            // Enable developer tools if requested
+            settings.set_enable_developer_extras(platform.enable_debug);
+
+            // Software rendering keeps the view usable on headless X servers such as Xvfb
+            if !platform.hardware_acceleration {
+                settings.set_hardware_acceleration_policy(HardwareAccelerationPolicy::Never);
+            }
+        }
+
+        #[cfg(feature = "serde")]
+        {
+            let web_view = web_view.downgrade();
+            reply_receiver.attach(None, move |script| {
+                if let Some(web_view) = web_view.upgrade() {
+                    web_view.run_javascript(&script, None::<&gio::Cancellable>, |_result| {});
+                }
+
+                glib::Continue(true)
+            });
+        }
+
+        // Hold events while a page loads. Failed loads finish too, so held events aren't stuck.
+        let event_queue = Rc::new(RefCell::new(script::EventQueue::default()));
+        {
+            let event_queue = event_queue.clone();
+            let outbox = outbox.clone();
+            web_view.connect_load_changed(move |web_view, load_event| match load_event {
+                LoadEvent::Started => {
+                    // Binary messages the old page never fetched won't be fetched now
+                    let leaving_page = event_queue.borrow_mut().start_loading();
+                    if leaving_page {
+                        outbox.clear();
+                    }
+                }
+                LoadEvent::Finished => {
+                    let scripts = event_queue.borrow_mut().finish_loading();
+                    for script in scripts {
+                        web_view.run_javascript(&script, None::<&gio::Cancellable>, |_result| {});
+                    }
+                }
+                _ => {}
+            });
+        }
+
+        // Let the navigation policy decide on navigations, including those opening new windows
+        if let Some(policy) = navigation_policy {
+            let policy = RefCell::new(policy);
+            web_view.connect_decide_policy(move |_, decision, decision_type| {
+                if !matches!(decision_type, PolicyDecisionType::NavigationAction | PolicyDecisionType::NewWindowAction) {
+                    return false;
+                }
+
+                let action = match decision.downcast_ref::<NavigationPolicyDecision>().and_then(|decision| decision.navigation_action()) {
+                    Some(action) => action,
+                    None => return false,
+                };
+
+                let action = NavigationAction {
+                    url: action.request().and_then(|request| request.uri()).map(|uri| uri.to_string()).unwrap_or_default(),
+                    navigation_type: navigation_type(action.navigation_type()),
+                    // Only navigations opening a new window are known to load a top-level document
+                    main_frame: match decision_type {
+                        PolicyDecisionType::NewWindowAction => Some(true),
+                        _ => None,
+                    },
+                };
+
+                match (policy.borrow_mut())(&action) {
+                    // Leave the decision to WebKit, as if there were no policy
+                    NavigationPolicy::Allow => return false,
+                    NavigationPolicy::Cancel => decision.ignore(),
+                    NavigationPolicy::OpenExternally => {
+                        decision.ignore();
+                        gio::AppInfo::launch_default_for_uri(&action.url, None::<&gio::AppLaunchContext>).ok();
+                    }
+                }
+
+                true
+            });
+        }
+
+        // Give each load an id, carried by all of its events
+        let navigation_state = Rc::new(RefCell::new(NavigationState::default()));
+        {
+            let navigation_state = navigation_state.clone();
+            let event_handler = event_handler.clone();
+            web_view.connect_load_changed(move |web_view, load_event| {
+                let url = web_view.uri().map(|uri| uri.to_string()).unwrap_or_default();
+
+                let mut state = navigation_state.borrow_mut();
+                match load_event {
+                    LoadEvent::Started => {
+                        state.id = navigation::next_id();
+                        state.provisional = true;
+                        state.failed = false;
+                    }
+                    LoadEvent::Committed | LoadEvent::Finished => state.provisional = false,
+                    _ => {}
+                }
+                state.url = url.clone();
+
+                let navigation = NavigationInfo {
+                    id: state.id,
+                    url,
+                    same_document: false,
+                };
+                let failed = state.failed;
+                drop(state);
+
+                // Failed loads still end with `Finished`, which releases the held events above,
+                // but aren't reported as finished
+                let event = match load_event {
+                    LoadEvent::Started => NavigationEvent::Start(navigation),
+                    LoadEvent::Redirected => NavigationEvent::Platform(PlatformNavigationEvent::Redirect(navigation)),
+                    LoadEvent::Committed => NavigationEvent::Commit(navigation),
+                    LoadEvent::Finished if !failed => NavigationEvent::Finish(navigation),
+                    _ => return,
+                };
+
+                event_handler.borrow_mut().handle_event(Event::Navigation(event));
+            });
+        }
+        {
+            let navigation_state = navigation_state.clone();
+            web_view.connect_load_failed(move |_, _, _, _| {
+                navigation_state.borrow_mut().failed = true;
+                // Let WebKit show its error page
+                false
+            });
+        }
+
+        // WebKitGTK doesn't report navigations within a document, such as `history.pushState`, so
+        // look for URL changes outside of loads. The URL changes just before a load starts, so only
+        // check once the load events have been handled.
+        web_view.connect_uri_notify(move |web_view| {
+            let web_view = web_view.downgrade();
+            let navigation_state = navigation_state.clone();
+            let event_handler = event_handler.clone();
+            glib::idle_add_local_once(move || {
+                let url = match web_view.upgrade().and_then(|web_view| web_view.uri()) {
+                    Some(uri) => uri.to_string(),
+                    None => return,
+                };
+
+                let mut state = navigation_state.borrow_mut();
+                if state.provisional || state.url == url {
+                    return;
+                }
+                state.url = url.clone();
+                drop(state);
+
+                let navigation = NavigationInfo {
+                    id: navigation::next_id(),
+                    url,
+                    same_document: true,
+                };
+                for event in [
+                    NavigationEvent::Start(navigation.clone()),
+                    NavigationEvent::Commit(navigation.clone()),
+                    NavigationEvent::Finish(navigation),
+                ] {
+                    event_handler.borrow_mut().handle_event(Event::Navigation(event));
+                }
+            });
+        });
+
+        // Embed a GTK toplevel into the winit window
+        let plug = gtk::Plug::new(xid as gtk::xlib::Window);
+        plug.add(&web_view);
+
+        let size = window_size(window, &plug);
+        plug.resize(size.0, size.1);
+        plug.show_all();
+
+        PlatformWebView {
+            plug,
+            web_view,
+            events,
+            event_queue,
+            outbox,
+            base_url,
+            size,
+        }
+    }
+
+    pub fn navigate(&mut self, target: NavigationTarget) {
+        match target {
+            NavigationTarget::Url(url) => self.web_view.load_uri(url),
+            NavigationTarget::Html(html) => self.web_view.load_html(html, Some(&self.base_url)),
+        }
+    }
+
+    pub fn execute(&mut self, js: impl AsRef<str>) {
+        self.web_view.run_javascript(js.as_ref(), None::<&gio::Cancellable>, |_result| {});
+    }
+
+    pub fn evaluate_with_result(&mut self, js: impl AsRef<str>, callback: script::ScriptCallback) {
+        // The callback runs on the main thread, where the script was started
+        let callback = glib::thread_guard::ThreadGuard::new(callback);
+        self.web_view.run_javascript(js.as_ref(), None::<&gio::Cancellable>, move |result| {
+            (callback.into_inner())(match result {
+                Ok(result) => Ok(result
+                    .js_value()
+                    .and_then(|value| value.to_json(0))
+                    .map(|json| json.to_string())
+                    .unwrap_or_else(|| "null".to_owned())),
+                Err(err) => Err(script_error(err.message())),
+            })
+        });
+    }
+
+    pub(crate) fn event_sink(&self) -> Rc<dyn Fn(Event)> {
+        self.events.clone()
+    }
+
+    pub(crate) fn emit(&mut self, script: String) {
+        let script = self.event_queue.borrow_mut().push(script);
+        if let Some(script) = script {
+            self.execute(script);
+        }
+    }
+
+    pub fn title(&self) -> Option<String> {
+        match WebViewExt::title(&self.web_view) {
+            Some(title) if !title.is_empty() => Some(title.to_string()),
+            _ => None,
+        }
+    }
+
+    /// Run pending GTK work and keep the embedded view sized to the window
+    pub(crate) fn pump_events(&mut self, window: &Window) {
+        let size = window_size(window, &self.plug);
+        if size != self.size {
+            self.size = size;
+            self.plug.resize(size.0, size.1);
+        }
+
+        while gtk::events_pending() {
+            gtk::main_iteration_do(false);
+        }
+    }
+}
+
+/// Get the size of the window in GTK's logical pixels
+fn window_size(window: &Window, plug: &gtk::Plug) -> (i32, i32) {
+    let size = window.inner_size();
+    let scale = plug.scale_factor().max(1);
+
+    (size.width as i32 / scale, size.height as i32 / scale)
+}
+
+/// Convert WebKit's navigation type to ours
+fn navigation_type(navigation_type: webkit2gtk::NavigationType) -> NavigationType {
+    match navigation_type {
+        webkit2gtk::NavigationType::LinkClicked => NavigationType::LinkClicked,
+        webkit2gtk::NavigationType::FormSubmitted => NavigationType::FormSubmitted,
+        webkit2gtk::NavigationType::BackForward => NavigationType::BackForward,
+        webkit2gtk::NavigationType::Reload => NavigationType::Reload,
+        webkit2gtk::NavigationType::FormResubmitted => NavigationType::FormResubmitted,
+        _ => NavigationType::Other,
+    }
+}
+
+/// Convert a message that isn't a string to JSON
+#[cfg(feature = "serde")]
+fn json_message(value: &javascriptcore::Value) -> Event {
+    match value.to_json(0) {
+        Some(json) => ipc::json_event(&json, None),
+        None => Event::MessageError(ipc::MessageError {
+            message: "The message can't be represented as JSON".to_owned(),
+            body: None,
+        }),
+    }
+}
+
+/// Convert a script failure, splitting off the location WebKitGTK may prefix it with,
+/// e.g. `winit:///app.js:3:10: ReferenceError: Can't find variable: x`
+fn script_error(message: &str) -> ScriptError {
+    for (index, _) in message.match_indices(": ") {
+        let location = &message[..index];
+        let numbers: Vec<u32> = location
+            .rsplit(':')
+            .map_while(|part| part.parse().ok())
+            .collect();
+
+        // The location ends with the line, or the line and the column
+        if location.contains(':') && (1..=2).contains(&numbers.len()) {
+            return ScriptError {
+                message: message[index + 2..].to_owned(),
+                line: numbers.last().copied(),
+            };
+        }
+    }
+
+    ScriptError {
+        message: message.to_owned(),
+        line: None,
+    }
+}
+
+/// Hand a scheme request to its handler
+fn handle_request(request: URISchemeRequest, converted: request::Request, request_handler: &mut request::BoxedHandler) {
+    let cancellation = converted.cancellation.clone();
+
+    // The handler may respond from another thread, so hop back onto the main loop to finish
+    let (sender, receiver) = glib::MainContext::channel(glib::PRIORITY_DEFAULT);
+    receiver.attach(None, move |response| {
+        match response {
+            Some(response) => finish_request(&request, response, cancellation.clone()),
+            None => {
+                let uri = request.uri().map(|uri| uri.to_string()).unwrap_or_default();
+                let mut error = glib::Error::new(gio::IOErrorEnum::NotFound, uri.as_str());
+                request.finish_error(&mut error);
+            }
+        }
+
+        glib::Continue(false)
+    });
+
+    let responder = request::Responder::new(converted.cancellation.clone(), move |response| {
+        sender.send(response).ok();
+    });
+    request_handler(converted, responder);
+}
+
+/// Complete a scheme request with a handler's response
+fn finish_request(
+    request: &URISchemeRequest,
+    response: request::Response<impl io::Read + Send + 'static>,
+    cancellation: request::CancellationToken,
+) {
+    // HEAD responses describe the body a `GET` would get, without sending it
+    let is_head = request.http_method().is_some_and(|method| method == "HEAD");
+    let len = if is_head || matches!(response.status, 204 | 304) {
+        0
+    } else {
+        response.content_length().map(|len| len as i64).unwrap_or(-1)
+    };
+
+    let mut headers = soup::MessageHeaders::new(soup::MessageHeadersType::Response);
+    for (name, value) in response.header_fields() {
+        headers.append(name.as_str(), value.as_str());
+    }
+
+    let request::Response { status, body, mime_type, .. } = response;
+    match stream_body(body, cancellation) {
+        Ok(stream) => {
+            let scheme_response = URISchemeResponse::new(&stream, len);
+            scheme_response.set_status(status as u32, None);
+            scheme_response.set_content_type(mime_type.as_str());
+            scheme_response.set_http_headers(&mut headers);
+
+            request.finish_with_response(&scheme_response);
+        }
+        Err(err) => {
+            let mut error = glib::Error::new(gio::IOErrorEnum::Failed, &err.to_string());
+            request.finish_error(&mut error);
+        }
+    }
+}
+
+/// Stream a body to WebKit through a socket, filled by a background thread as WebKit drains it
+fn stream_body(
+    mut body: impl io::Read + Send + 'static,
+    cancellation: request::CancellationToken,
+) -> io::Result<gio::UnixInputStream> {
+    let (reader, mut writer) = UnixStream::pair()?;
+
+    thread::spawn(move || {
+        // Writing fails once WebKit closes its end, i.e. when the load is cancelled
+        if let Err(err) = io::copy(&mut body, &mut writer) {
+            if matches!(err.kind(), io::ErrorKind::BrokenPipe | io::ErrorKind::ConnectionReset) {
+                cancellation.cancel();
+            }
+        }
+    });
+
+    Ok(unsafe { gio::UnixInputStream::take_fd(reader) })
+}
+
+/// Convert a WebKitGTK scheme request into a `request::Request`, without its body
+fn convert_request(request: &URISchemeRequest) -> request::Request {
+    let method = request.http_method().map(|method| method.to_string()).unwrap_or_else(|| "GET".to_owned());
+    let url = request.uri().map(|uri| uri.to_string()).unwrap_or_default();
+    let mut converted = request::Request::new(method, url);
+
+    if let Some(mut headers) = request.http_headers() {
+        headers.foreach(|name, value| converted.headers.append(name, value));
+    }
+
+    converted
+}
+
+/// Read the body of a scheme request on the main loop, a chunk at a time
+#[cfg(feature = "v2_40")]
+fn read_body(request: &URISchemeRequest, done: impl FnOnce(Result<Vec<u8>, glib::Error>) + 'static) {
+    let body: Option<gio::InputStream> = unsafe {
+        from_glib_full(webkit_uri_scheme_request_get_http_body(request.to_glib_none().0))
+    };
+
+    match body {
+        Some(body) => read_chunks(body, Vec::new(), done),
+        None => done(Ok(Vec::new())),
+    }
+}
+
+/// Request bodies can only be read from WebKitGTK 2.40, so without it they're always empty
+#[cfg(not(feature = "v2_40"))]
+fn read_body(_request: &URISchemeRequest, done: impl FnOnce(Result<Vec<u8>, glib::Error>) + 'static) {
+    done(Ok(Vec::new()))
+}
+
+/// Read the rest of a stream, failing if any read does
+#[cfg(feature = "v2_40")]
+fn read_chunks(
+    stream: gio::InputStream,
+    mut body: Vec<u8>,
+    done: impl FnOnce(Result<Vec<u8>, glib::Error>) + 'static,
+) {
+    stream.clone().read_bytes_async(64 * 1024, glib::PRIORITY_DEFAULT, None::<&gio::Cancellable>, move |chunk| match chunk {
+        Ok(chunk) if chunk.is_empty() => done(Ok(body)),
+        Ok(chunk) => {
+            body.extend_from_slice(&chunk);
+            read_chunks(stream, body, done);
+        }
+        Err(error) => done(Err(error)),
+    });
+}
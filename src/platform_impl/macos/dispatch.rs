@@ -0,0 +1,28 @@
+use std::ffi::c_void;
+
+extern "C" {
+    static _dispatch_main_q: c_void;
+
+    fn dispatch_async_f(queue: *const c_void, context: *mut c_void, work: extern "C" fn(*mut c_void));
+
+    fn pthread_main_np() -> i32;
+}
+
+/// Run a closure on the main thread: immediately if already on it,
+/// otherwise as soon as the main run loop gets to it
+pub fn run_on_main(f: impl FnOnce() + Send + 'static) {
+    if unsafe { pthread_main_np() } != 0 {
+        return f();
+    }
+
+    extern "C" fn trampoline(context: *mut c_void) {
+        let f = unsafe { Box::from_raw(context as *mut Box<dyn FnOnce() + Send>) };
+        f();
+    }
+
+    let f: Box<Box<dyn FnOnce() + Send>> = Box::new(Box::new(f));
+
+    unsafe {
+        dispatch_async_f(&_dispatch_main_q as *const c_void, Box::into_raw(f) as *mut c_void, trampoline);
+    }
+}
@@ -56,6 +56,7 @@ macro_rules! def_class {
                 ivar $ivar_name:ident: $ivar_type:ty;
             )*
             $(
+                $(#[$fn_meta:meta])*
                 fn $fn_name:ident($this:ident$(, $arg_name:ident$( $binding:ident)?: $arg_type:ty)*) $(-> $ret_type:ty)? $body:block
             )*
         }
@@ -66,6 +67,7 @@ macro_rules! def_class {
 
             impl$(<$($generic$(: $($generic_lt +)? $generic_constraint)?),+>)* $name$(<$($generic),+>)* {
                 $(
+                    $(#[$fn_meta])*
                     #[doc(hidden)]
                     #[allow(dead_code, non_snake_case)]
                     extern fn [<$fn_name $($arg_name)*>](
@@ -81,6 +83,7 @@ macro_rules! def_class {
                     REGISTER_ONCE.call_once(|| unsafe {
                         let mut decl = ::objc::declare::ClassDecl::new(::std::any::type_name::<Self>(), class!($superclass)).unwrap();
                         $(
+                            $(#[$fn_meta])*
                             decl.add_method(method_sel!($fn_name$(, $arg_name)*), Self::[<$fn_name $($arg_name)*>] as extern fn(&mut ::objc::runtime::Object, ::objc::runtime::Sel$(, $arg_type)*) $(-> $ret_type)*);
                         )*
 
@@ -1,13 +1,14 @@
 use crate::*;
-use block::ConcreteBlock;
+use block::{Block, ConcreteBlock};
 use core_graphics::display::CGRect;
 use objc::{rc::StrongPtr, runtime::Object};
 use objc_foundation::{INSData, INSString, NSData, NSString};
-use std::ffi::c_void;
+use std::{cell::RefCell, collections::HashMap, ffi::c_void, io, rc::Rc, sync::mpsc, thread};
 use winit::{platform::macos::WindowExtMacOS, window::Window};
 
 #[macro_use]
 mod macros;
+mod dispatch;
 
 #[derive(Debug, Default)]
 pub struct PlatformWebViewBuilder {
@@ -20,28 +21,22 @@ impl PlatformWebViewBuilder {
     }
 }
 
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone)]
 pub enum PlatformNavigationEvent {
-    /// Triggered when a redirect has taken place
-    Redirect,
+    /// Triggered when a redirect has taken place, with the URL redirected to
+    Redirect(NavigationInfo),
 }
 
 #[derive(Debug, Clone, Copy)]
 pub enum PlatformEvent {}
 
 pub struct PlatformWebView {
-    pub(crate) _delegate: StrongPtr,
+    pub(crate) delegate: StrongPtr,
     pub(crate) web_view: StrongPtr,
-}
-
-pub struct PlatformRequest<'a> {
-    pub uri: &'a str
-}
-
-impl<'a> PlatformRequest<'a> {
-    pub fn as_uri(&self) -> &str {
-        self.uri
-    }
+    /// Scripts emitting events, held back while the page loads
+    event_queue: Rc<RefCell<script::EventQueue>>,
+    pub(crate) outbox: binary::Outbox,
+    base_url: String,
 }
 
 impl PlatformWebView {
@@ -50,22 +45,56 @@ impl PlatformWebView {
         event_handler: impl EventHandler,
         window: &mut Window,
     ) -> Self {
-        let WebViewBuilder { init_scripts, request_handler, .. } = builder;
+        let WebViewBuilder {
+            init_scripts,
+            channels,
+            navigation_policy,
+            request_handler,
+            mut schemes,
+            base_url,
+            #[cfg(feature = "serde")]
+            commands,
+            platform,
+        } = builder;
 
         let view = window.ns_view() as *mut Object;
 
+        // The delegate and the binary message handler share the event handler
+        let event_handler = Rc::new(RefCell::new(event_handler));
+        let events: Rc<dyn Fn(Event)> = Rc::new(move |event| event_handler.borrow_mut().handle_event(event));
+
+        // Binary messages travel over their own scheme, in both directions
+        let outbox = binary::Outbox::default();
+        let binary_handler = binary::BinaryHandler::new(events.clone(), outbox.clone(), schemes.keys(), &base_url);
+        schemes.insert(binary::SCHEME.to_owned(), request::boxed(binary_handler));
+
+        let event_queue = Rc::new(RefCell::new(script::EventQueue::default()));
+
         unsafe {
-            let delegate = WinitDelegate::new(event_handler, request_handler);
+            let handlers = Handlers {
+                requests: request::scheme_handlers(request_handler, schemes),
+                #[cfg(feature = "serde")]
+                commands,
+                event_queue: event_queue.clone(),
+                outbox: outbox.clone(),
+                navigation_policy,
+                navigations: HashMap::new(),
+            };
+            let scheme_names: Vec<String> = handlers.requests.keys().cloned().collect();
+            let delegate = WinitDelegate::new(move |event| events(event), handlers);
 
             let _: () = msg_send![view, setAutoresizesSubviews: objc::runtime::YES];
 
             let config: *mut Object = msg_send![class!(WKWebViewConfiguration), new];
 
-            let winit_scheme = NSString::from_str("winit");
-            let _: () = msg_send![config, setURLSchemeHandler: delegate forURLScheme: winit_scheme];
+            // Route each custom scheme to the delegate, which picks the scheme's handler
+            for scheme in scheme_names {
+                let scheme = NSString::from_str(scheme.as_str());
+                let _: () = msg_send![config, setURLSchemeHandler: delegate forURLScheme: scheme];
+            }
 
             // Enable developer tools if requested
-            if builder.platform.enable_debug {
+            if platform.enable_debug {
                 let preferences: *mut Object = msg_send![config, preferences];
                 let number: *mut Object =
                     msg_send![class!(NSNumber), numberWithBool: objc::runtime::YES];
@@ -79,11 +108,31 @@ impl PlatformWebView {
             let handler_name = NSString::from_str("WinitMessageHandler");
             let _: () = msg_send![manager, addScriptMessageHandler: delegate name: handler_name];
 
+            // Register the named channels
+            for channel in &channels {
+                let handler_name = NSString::from_str(channel);
+                let _: () = msg_send![manager, addScriptMessageHandler: delegate name: handler_name];
+            }
+
+            // Register the handlers behind `window.winit.postMessage` and `window.winit.invoke`
+            #[cfg(feature = "serde")]
+            for name in &[ipc::JSON_HANDLER, ipc::INVOKE_HANDLER] {
+                let handler_name = NSString::from_str(name);
+                let _: () = msg_send![manager, addScriptMessageHandler: delegate name: handler_name];
+            }
+
+            // The JSON and binary bridges run before the page's own init scripts. Replies are only
+            // evaluated in the main frame, so iframes don't get `window.winit`, whose calls would never settle.
+            let init_scripts = init_scripts.into_iter().map(|script| (script, objc::runtime::NO));
+            #[cfg(feature = "serde")]
+            let init_scripts = std::iter::once((ipc::BRIDGE_SCRIPT.to_owned(), objc::runtime::YES)).chain(init_scripts);
+            let init_scripts = std::iter::once((binary::SCRIPT.to_owned(), objc::runtime::NO)).chain(init_scripts);
+
             // Register all init scripts
-            for script in init_scripts {
+            for (script, main_frame_only) in init_scripts {
                 let script = NSString::from_str(script.as_str());
                 let wk_script: *mut Object = msg_send![class!(WKUserScript), alloc];
-                let wk_script: *mut Object = msg_send![wk_script, initWithSource: script injectionTime: 0 forMainFrameOnly: objc::runtime::NO];
+                let wk_script: *mut Object = msg_send![wk_script, initWithSource: script injectionTime: 0 forMainFrameOnly: main_frame_only];
 
                 let _: () = msg_send![manager, addUserScript: wk_script];
             }
@@ -99,8 +148,11 @@ impl PlatformWebView {
             let _: () = msg_send![view, addSubview: web_view];
 
             PlatformWebView {
-                _delegate: StrongPtr::new(delegate),
+                delegate: StrongPtr::new(delegate),
                 web_view: StrongPtr::new(web_view),
+                event_queue,
+                outbox,
+                base_url,
             }
         }
     }
@@ -117,7 +169,7 @@ impl PlatformWebView {
             },
             NavigationTarget::Html(html) => unsafe {
                 let html = NSString::from_str(html);
-                let base_url = NSString::from_str("winit://");
+                let base_url = NSString::from_str(self.base_url.as_str());
                 let base_url: *mut Object = msg_send![class!(NSURL), URLWithString: base_url];
 
                 let _: *mut Object = msg_send![*self.web_view, loadHTMLString: html baseURL: base_url];
@@ -126,17 +178,41 @@ impl PlatformWebView {
     }
 
     pub fn execute(&mut self, js: impl AsRef<str>) {
-        fn do_nothing_handler(_result: *mut Object, _error: *mut Object) {}
+        unsafe { evaluate(*self.web_view, js.as_ref()) }
+    }
+
+    pub fn evaluate_with_result(&mut self, js: impl AsRef<str>, callback: script::ScriptCallback) {
+        // Blocks may be called more than once as far as Rust knows, so only the first call completes
+        let callback = RefCell::new(Some(callback));
+        let block = ConcreteBlock::new(move |result: *mut Object, error: *mut Object| {
+            if let Some(callback) = callback.borrow_mut().take() {
+                callback(unsafe { script_result(result, error) });
+            }
+        });
+        let block = block.copy();
 
         unsafe {
             let js = NSString::from_str(js.as_ref());
-            let block = ConcreteBlock::new(do_nothing_handler);
-            let block = block.copy();
-
             let _: () = msg_send![*self.web_view, evaluateJavaScript: js completionHandler: block];
         }
     }
 
+    /// Get a function that delivers events to the delegate's event handler
+    pub(crate) fn event_sink(&self) -> Rc<dyn Fn(Event)> {
+        let delegate = self.delegate.clone();
+        Rc::new(move |event| unsafe {
+            let event = Box::into_raw(Box::new(event)) as *mut c_void;
+            let _: () = msg_send![*delegate, handleEvent: event];
+        })
+    }
+
+    pub(crate) fn emit(&mut self, script: String) {
+        let script = self.event_queue.borrow_mut().push(script);
+        if let Some(script) = script {
+            self.execute(script);
+        }
+    }
+
     pub fn title(&self) -> Option<String> {
         unsafe {
             let title: *const NSString = msg_send![*self.web_view, title];
@@ -154,15 +230,33 @@ impl PlatformWebView {
     }
 }
 
+/// Whether WebKit loads a scheme itself, in which case registering a handler for it throws
+pub(crate) fn handles_scheme(scheme: &str) -> bool {
+    let scheme = NSString::from_str(scheme);
+    let handles: objc::runtime::BOOL = unsafe { msg_send![class!(WKWebView), handlesURLScheme: scheme] };
+    handles == objc::runtime::YES
+}
+
+/// Run a script in a web view, ignoring its result
+unsafe fn evaluate(web_view: *mut Object, js: &str) {
+    fn do_nothing_handler(_result: *mut Object, _error: *mut Object) {}
+
+    let js = NSString::from_str(js);
+    let block = ConcreteBlock::new(do_nothing_handler);
+    let block = block.copy();
+
+    let _: () = msg_send![web_view, evaluateJavaScript: js completionHandler: block];
+}
+
 def_class! {
-    class WinitDelegate<T: EventHandler, K: request::RequestHandler>: NSObject, WKNavigationDelegate {
+    class WinitDelegate<T: EventHandler>: NSObject, WKNavigationDelegate {
         ivar event_handler: *mut c_void;
-        ivar request_handler: *mut c_void;
+        ivar handlers: *mut c_void;
 
-        fn initWithHandler(this, event_handler: *mut c_void, requestHandler request_handler: *mut c_void) -> *mut Object {
+        fn initWithHandler(this, event_handler: *mut c_void, handlers handlers: *mut c_void) -> *mut Object {
             unsafe {
                 this.set_ivar("event_handler", event_handler);
-                this.set_ivar("request_handler", request_handler);
+                this.set_ivar("handlers", handlers);
 
                 msg_send![this, init]
             }
@@ -172,13 +266,24 @@ def_class! {
             unsafe {
                 // Get a pointer to the internal state
                 let event_handler = *this.get_ivar::<*mut c_void>("event_handler");
+                let handlers = *this.get_ivar::<*mut c_void>("handlers");
                 // Drop the internal state
                 Box::from_raw(event_handler as *mut T);
+                Box::from_raw(handlers as *mut Handlers);
 
                 let _: () = msg_send![super(this, class!(NSObject)), dealloc];
             }
         }
 
+        fn handleEvent(this, event: *mut c_void) {
+            unsafe {
+                let event_handler: *mut c_void = *this.get_ivar("event_handler");
+                let event_handler = &mut *(event_handler as *mut T);
+
+                event_handler.handle_event(*Box::from_raw(event as *mut Event));
+            }
+        }
+
         fn userContentController(this, _user_content_controller: *mut Object, didReceiveScriptMessage message: *mut Object) {
             unsafe {
                 let event_handler: *mut c_void = *this.get_ivar("event_handler");
@@ -186,98 +291,711 @@ def_class! {
 
                 let body: *mut Object = msg_send![message, body];
                 let is_str: objc::runtime::BOOL = msg_send![body, isKindOfClass: class!(NSString)];
+                let name: &NSString = msg_send![message, name];
+
+                #[cfg(feature = "serde")]
+                {
+                    // `window.winit.postMessage` sends JSON text to its own handler
+                    if name.as_str() == ipc::JSON_HANDLER {
+                        if is_str == objc::runtime::YES {
+                            event_handler.handle_event(ipc::json_event((*(body as *mut NSString)).as_str(), message_source(message)));
+                        }
+
+                        return;
+                    }
+
+                    // `window.winit.invoke` calls a command, which replies by running a script in the view
+                    if name.as_str() == ipc::INVOKE_HANDLER {
+                        if is_str == objc::runtime::YES {
+                            let handlers: *mut c_void = *this.get_ivar("handlers");
+                            let handlers = &mut *(handlers as *mut Handlers);
+
+                            // Keep the view alive until the command replies, possibly from another thread
+                            let web_view: *mut Object = msg_send![message, webView];
+                            let _: *mut Object = msg_send![web_view, retain];
+                            let web_view = web_view as usize;
+
+                            let send = move |script: String| dispatch::run_on_main(move || {
+                                let web_view = web_view as *mut Object;
+                                evaluate(web_view, &script);
+                                let _: () = msg_send![web_view, release];
+                            });
+
+                            let call = (*(body as *mut NSString)).as_str();
+                            if let Err(err) = ipc::dispatch(&mut handlers.commands, call, message_source(message), send) {
+                                // The call couldn't be read, so no reply will release the view
+                                let _: () = msg_send![web_view as *mut Object, release];
+                                event_handler.handle_event(Event::MessageError(err));
+                            }
+                        }
+
+                        return;
+                    }
+                }
+
+                // Messages to named channels are tagged with the channel
+                if name.as_str() != "WinitMessageHandler" {
+                    if is_str == objc::runtime::YES {
+                        event_handler.handle_event(Event::ChannelMessage {
+                            channel: name.as_str().to_owned(),
+                            message: (*(body as *mut NSString)).as_str().to_owned(),
+                            source: message_source(message)
+                        });
+                    }
+
+                    return;
+                }
+
+                #[cfg(feature = "serde")]
+                if is_str != objc::runtime::YES {
+                    return event_handler.handle_event(json_message(body, message_source(message)));
+                }
+
                 if is_str == objc::runtime::YES {
-                    event_handler.handle_event(Event::Message((*(body as *mut NSString)).as_str().to_owned()));
+                    let text = (*(body as *mut NSString)).as_str().to_owned();
+                    event_handler.handle_event(Event::Message(text, message_source(message)));
+                }
+            }
+        }
+
+        fn webView(this, _web_view: *mut Object, decidePolicyForNavigationAction action: *mut Object, decisionHandler decision_handler: *mut Object) {
+            unsafe {
+                let handlers: *mut c_void = *this.get_ivar("handlers");
+                let handlers = &mut *(handlers as *mut Handlers);
+
+                let request: *mut Object = msg_send![action, request];
+                let url: *mut Object = msg_send![request, URL];
+
+                let policy = match &mut handlers.navigation_policy {
+                    Some(policy) => policy(&convert_navigation_action(action, url)),
+                    None => NavigationPolicy::Allow
+                };
+
+                if policy == NavigationPolicy::OpenExternally && !url.is_null() {
+                    let workspace: *mut Object = msg_send![class!(NSWorkspace), sharedWorkspace];
+                    let _: objc::runtime::BOOL = msg_send![workspace, openURL: url];
                 }
+
+                // The handler takes a WKNavigationActionPolicy: 0 cancels, 1 allows
+                let decision_handler = &*(decision_handler as *const Block<(isize,), ()>);
+                decision_handler.call((if policy == NavigationPolicy::Allow { 1 } else { 0 },));
             }
         }
 
-        fn webView(this, _web_view: *mut Object, didStartProvisionalNavigation _navigation: *mut Object) {
+        fn webView(this, web_view: *mut Object, didStartProvisionalNavigation navigation: *mut Object) {
+            let navigation = unsafe {
+                let handlers: *mut c_void = *this.get_ivar("handlers");
+                let handlers = &mut *(handlers as *mut Handlers);
+                // Binary messages the old page never fetched won't be fetched now
+                let leaving_page = handlers.event_queue.borrow_mut().start_loading();
+                if leaving_page {
+                    handlers.outbox.clear();
+                }
+
+                // Each navigation gets a new id, even if WebKit reuses the object
+                handlers.navigations.remove(&(navigation as usize));
+                navigation_info(handlers, web_view, navigation)
+            };
+
             let event_handler = unsafe {
                 let event_handler: *mut c_void = *this.get_ivar("event_handler");
                 &mut *(event_handler as *mut T)
             };
 
-            event_handler.handle_event(Event::Navigation(NavigationEvent::Start));
+            event_handler.handle_event(Event::Navigation(NavigationEvent::Start(navigation)));
         }
 
-        fn webView(this, _web_view: *mut Object, didCommitNavigation _navigation: *mut Object) {
+        fn webView(this, web_view: *mut Object, didReceiveServerRedirectForProvisionalNavigation navigation: *mut Object) {
+            let navigation = unsafe {
+                let handlers: *mut c_void = *this.get_ivar("handlers");
+                navigation_info(&mut *(handlers as *mut Handlers), web_view, navigation)
+            };
+
             let event_handler = unsafe {
                 let event_handler: *mut c_void = *this.get_ivar("event_handler");
                 &mut *(event_handler as *mut T)
             };
 
-            event_handler.handle_event(Event::Navigation(NavigationEvent::Commit));
+            event_handler.handle_event(Event::Navigation(NavigationEvent::Platform(PlatformNavigationEvent::Redirect(navigation))));
         }
 
-        fn webView(this, _web_view: *mut Object, didFinishNavigation _navigation: *mut Object) {
+        fn webView(this, web_view: *mut Object, didCommitNavigation navigation: *mut Object) {
+            let navigation = unsafe {
+                let handlers: *mut c_void = *this.get_ivar("handlers");
+                navigation_info(&mut *(handlers as *mut Handlers), web_view, navigation)
+            };
+
             let event_handler = unsafe {
                 let event_handler: *mut c_void = *this.get_ivar("event_handler");
                 &mut *(event_handler as *mut T)
             };
 
-            event_handler.handle_event(Event::Navigation(NavigationEvent::Finish));
+            event_handler.handle_event(Event::Navigation(NavigationEvent::Commit(navigation)));
         }
 
-        fn webView(this, _web_view: *mut Object, startURLSchemeTask task: *mut Object) {
-            const URL_ERROR_NOT_FOUND: isize = -1100;
-            const URL_ERROR_UNKNOWN: isize = -1;
+        fn webView(this, web_view: *mut Object, didFinishNavigation navigation: *mut Object) {
+            let navigation = unsafe {
+                let handlers: *mut c_void = *this.get_ivar("handlers");
+                let handlers = &mut *(handlers as *mut Handlers);
+
+                deliver_held_events(handlers, web_view);
+
+                let info = navigation_info(handlers, web_view, navigation);
+                handlers.navigations.remove(&(navigation as usize));
+                info
+            };
+
+            let event_handler = unsafe {
+                let event_handler: *mut c_void = *this.get_ivar("event_handler");
+                &mut *(event_handler as *mut T)
+            };
+
+            event_handler.handle_event(Event::Navigation(NavigationEvent::Finish(navigation)));
+        }
 
-            use std::io::Read;
+        fn webView(this, web_view: *mut Object, didFailProvisionalNavigation navigation: *mut Object, withError _error: *mut Object) {
+            unsafe {
+                let handlers: *mut c_void = *this.get_ivar("handlers");
+                let handlers = &mut *(handlers as *mut Handlers);
+
+                // The load is over, so hand the held events to whichever page is showing
+                deliver_held_events(handlers, web_view);
+                handlers.navigations.remove(&(navigation as usize));
+            }
+        }
+
+        fn webView(this, web_view: *mut Object, didFailNavigation navigation: *mut Object, withError _error: *mut Object) {
+            unsafe {
+                let handlers: *mut c_void = *this.get_ivar("handlers");
+                let handlers = &mut *(handlers as *mut Handlers);
+
+                // The load is over, so hand the held events to whichever page is showing
+                deliver_held_events(handlers, web_view);
+                handlers.navigations.remove(&(navigation as usize));
+            }
+        }
+
+        // WKNavigationDelegate has no public callback for `history.pushState` or anchor links,
+        // so this relies on the private one Safari uses
+        #[cfg(feature = "macos-private-api")]
+        fn _webView(this, web_view: *mut Object, navigation _navigation: *mut Object, didSameDocumentNavigation _navigation_type: isize) {
+            let navigation = NavigationInfo {
+                id: navigation::next_id(),
+                url: unsafe {
+                    let url: *mut Object = msg_send![web_view, URL];
+                    url_string(url)
+                },
+                same_document: true
+            };
+
+            let event_handler = unsafe {
+                let event_handler: *mut c_void = *this.get_ivar("event_handler");
+                &mut *(event_handler as *mut T)
+            };
+
+            event_handler.handle_event(Event::Navigation(NavigationEvent::Start(navigation.clone())));
+            event_handler.handle_event(Event::Navigation(NavigationEvent::Commit(navigation.clone())));
+            event_handler.handle_event(Event::Navigation(NavigationEvent::Finish(navigation)));
+        }
 
+        fn webView(this, _web_view: *mut Object, startURLSchemeTask task: *mut Object) {
             unsafe {
                 let request: *mut Object = msg_send![task, request];
-                let url: *mut Object = msg_send![request, URL];
-                let path: &NSString = msg_send![url, path];
-                let url: &NSString = msg_send![url, absoluteString];
 
-                let request_handler: *mut c_void = *this.get_ivar("request_handler");
-                let request_handler = &mut *(request_handler as *mut K);
+                let (request, body_stream) = convert_request(request);
+                let task = SchemeTask::start(task, request.cancellation.clone());
 
-                let error_code = if let Some(request::Response { mut body, mime_type }) = request_handler.handle_platform_request(PlatformRequest { uri: path.as_str() }) {
-                    let mut buffer = Vec::new();
-                    if let Ok(content_len) = body.read_to_end(&mut buffer) {
-                        let data = NSData::from_vec(buffer);
+                let body_stream = match body_stream {
+                    Some(body_stream) => body_stream,
+                    None => return handle_request(this, request, task)
+                };
 
-                        let url: *mut Object = msg_send![class!(NSURL), URLWithString: url];
-                        let mime_type = NSString::from_str(mime_type.as_str());
-                        let response: *mut Object = msg_send![class!(NSURLResponse), alloc];
+                // Streamed bodies are read off the main thread, then handled back on it.
+                // The delegate owns the handlers, so keep it alive until then.
+                let delegate: *mut Object = msg_send![this, retain];
+                let delegate = delegate as usize;
+                body_stream.read(move |body| dispatch::run_on_main(move || unsafe {
+                    let delegate = delegate as *mut Object;
+                    match body {
+                        Ok(body) => handle_request(&mut *delegate, request.with_body(body), task),
+                        Err(_) => task.fail(URL_ERROR_UNKNOWN)
+                    }
 
-                        let response: *mut Object = msg_send![response, initWithURL: url MIMEType: mime_type expectedContentLength: content_len textEncodingName: std::ptr::null_mut::<Object>()];
-                        let _: () = msg_send![task, didReceiveResponse: response];
+                    let _: () = msg_send![delegate, release];
+                }));
+            }
+        }
 
-                        let _: () = msg_send![task, didReceiveData: data];
-                        let _: () = msg_send![task, didFinish];
+        fn webView(_this, _web_view: *mut Object, stopURLSchemeTask task: *mut Object) {
+            SchemeTask::stop(task);
+        }
+    }
+}
 
-                        return;
+/// Hand a scheme request to the handler for its scheme
+unsafe fn handle_request(delegate: &mut Object, request: request::Request, task: SchemeTask) {
+    let handlers: *mut c_void = *delegate.get_ivar("handlers");
+    let handlers = &mut *(handlers as *mut Handlers);
+
+    // The handler may respond right away, or later from another thread
+    let responder = request::Responder::new(request.cancellation.clone(), move |response| task.respond(response));
+    match handlers.requests.get_mut(&request.scheme().to_ascii_lowercase()) {
+        Some(request_handler) => request_handler(request, responder),
+        None => responder.respond(None)
+    }
+}
+
+/// The handlers the delegate routes requests and script calls to
+struct Handlers {
+    /// The request handler for each custom scheme, by scheme
+    requests: HashMap<String, request::BoxedHandler>,
+    /// The handler for each command scripts can call, by name
+    #[cfg(feature = "serde")]
+    commands: ipc::Commands,
+    /// Scripts emitting events, shared with the web view
+    event_queue: Rc<RefCell<script::EventQueue>>,
+    /// Binary messages waiting for the page to fetch them
+    outbox: binary::Outbox,
+    /// Decides whether navigations go ahead
+    navigation_policy: Option<navigation::PolicyHandler>,
+    /// The id of each navigation in progress, by the address of its WKNavigation
+    navigations: HashMap<usize, u64>,
+}
+
+const URL_ERROR_NOT_FOUND: isize = -1100;
+const URL_ERROR_UNKNOWN: isize = -1;
+
+/// `WKErrorJavaScriptResultTypeIsUnsupported`, for results such as functions that can't leave the page
+const WK_ERROR_RESULT_TYPE_UNSUPPORTED: isize = 5;
+
+/// The size of the chunks response bodies are streamed to the webview in
+const CHUNK_SIZE: usize = 64 * 1024;
+
+thread_local! {
+    /// The cancellation tokens of the scheme tasks in flight, by task
+    static SCHEME_TASKS: RefCell<HashMap<usize, request::CancellationToken>> = RefCell::new(HashMap::new());
+}
+
+/// A retained `WKURLSchemeTask` that can be completed from any thread
+struct SchemeTask(*mut Object, request::CancellationToken);
+
+// Tasks are only ever messaged, and released, from the main thread
+unsafe impl Send for SchemeTask {}
+
+impl SchemeTask {
+    /// Track a task, so that `stopURLSchemeTask:` can cancel it
+    fn start(task: *mut Object, cancellation: request::CancellationToken) -> Self {
+        unsafe {
+            let _: *mut Object = msg_send![task, retain];
+        }
+
+        SCHEME_TASKS.with(|tasks| tasks.borrow_mut().insert(task as usize, cancellation.clone()));
+        SchemeTask(task, cancellation)
+    }
+
+    /// Cancel a task that WebKit has stopped. It must not be messaged after this.
+    fn stop(task: *mut Object) {
+        let cancellation = SCHEME_TASKS.with(|tasks| tasks.borrow_mut().remove(&(task as usize)));
+        if let Some(cancellation) = cancellation {
+            cancellation.cancel();
+        }
+    }
+
+    fn is_stopped(&self) -> bool {
+        self.1.is_cancelled()
+    }
+
+    /// Send the response head from the main thread, then stream the body
+    fn respond(self, response: Option<request::Response<impl io::Read + Send + 'static>>) {
+        let response = match response {
+            Some(response) => response,
+            None => return self.fail(URL_ERROR_NOT_FOUND),
+        };
+
+        dispatch::run_on_main(move || unsafe {
+            if self.is_stopped() {
+                return self.untrack();
+            }
+
+            let request: *mut Object = msg_send![self.0, request];
+            let url: *mut Object = msg_send![request, URL];
+
+            // A dictionary holds one value per name, so repeated `Set-Cookie` fields are joined
+            // the way `NSHTTPURLResponse` joins them itself
+            let mut fields: Vec<(String, String)> = Vec::new();
+            for (name, value) in response.header_fields() {
+                match fields.iter_mut().find(|(key, _)| *key == name) {
+                    Some((_, existing)) => *existing = format!("{}, {}", existing, value),
+                    None => fields.push((name, value))
+                }
+            }
+
+            let headers: *mut Object = msg_send![class!(NSMutableDictionary), dictionary];
+            for (name, value) in fields {
+                let name = NSString::from_str(name.as_str());
+                let value = NSString::from_str(value.as_str());
+                let _: () = msg_send![headers, setObject: value forKey: name];
+            }
+
+            let http_version = NSString::from_str("HTTP/1.1");
+            let http_response: *mut Object = msg_send![class!(NSHTTPURLResponse), alloc];
+            let http_response: *mut Object = msg_send![http_response, initWithURL: url statusCode: response.status as isize HTTPVersion: http_version headerFields: headers];
+            let _: () = msg_send![self.0, didReceiveResponse: http_response];
+
+            self.stream(response.body);
+        });
+    }
+
+    /// Read the body on a background thread, handing each chunk to the task as it's produced
+    fn stream(self, mut body: impl io::Read + Send + 'static) {
+        thread::spawn(move || {
+            let (ack_sender, ack_receiver) = mpsc::channel();
+            let mut chunk = vec![0u8; CHUNK_SIZE];
+
+            loop {
+                // Stop reading as soon as the page loses interest, releasing the task on the main thread
+                if self.is_stopped() {
+                    return dispatch::run_on_main(move || drop(self));
+                }
+
+                match body.read(&mut chunk) {
+                    Ok(0) => return self.finish(),
+                    Ok(len) => {
+                        let task = self.0 as usize;
+                        let cancellation = self.1.clone();
+                        let data = chunk[..len].to_vec();
+                        let ack_sender = ack_sender.clone();
+
+                        dispatch::run_on_main(move || unsafe {
+                            if !cancellation.is_cancelled() {
+                                let data = NSData::from_vec(data);
+                                let _: () = msg_send![task as *mut Object, didReceiveData: data];
+                            }
+
+                            ack_sender.send(()).ok();
+                        });
+
+                        // Wait for the chunk to be delivered, so a fast reader can't outrun the webview
+                        ack_receiver.recv().ok();
                     }
+                    Err(err) if err.kind() == io::ErrorKind::Interrupted => continue,
+                    Err(_) => return self.fail(URL_ERROR_UNKNOWN)
+                }
+            }
+        });
+    }
 
-                    URL_ERROR_UNKNOWN
-                } else {
-                    URL_ERROR_NOT_FOUND
-                };
+    fn finish(self) {
+        dispatch::run_on_main(move || unsafe {
+            if !self.is_stopped() {
+                let _: () = msg_send![self.0, didFinish];
+            }
+
+            self.untrack();
+        });
+    }
 
+    fn fail(self, error_code: isize) {
+        dispatch::run_on_main(move || unsafe {
+            if !self.is_stopped() {
                 let domain = NSString::from_str("NSURLErrorDomain");
                 let error: *mut Object = msg_send![class!(NSError), errorWithDomain: domain code: error_code userInfo: std::ptr::null_mut::<Object>()];
 
-                let _: () = msg_send![task, didFailWithError: error];
+                let _: () = msg_send![self.0, didFailWithError: error];
             }
+
+            self.untrack();
+        });
+    }
+
+    /// Stop tracking a completed task. Must be called on the main thread.
+    fn untrack(&self) {
+        SCHEME_TASKS.with(|tasks| tasks.borrow_mut().remove(&(self.0 as usize)));
+    }
+}
+
+impl Drop for SchemeTask {
+    fn drop(&mut self) {
+        unsafe {
+            let _: () = msg_send![self.0, release];
         }
+    }
+}
 
-        fn webView(_this, _web_view: *mut Object, stopUrlSchemeTask _task: *mut Object) {
-            return;
+/// Convert a message that isn't a string to JSON
+#[cfg(feature = "serde")]
+unsafe fn json_message(body: *mut Object, source: Option<MessageSource>) -> Event {
+    match to_json(body) {
+        Some(json) => ipc::json_event(&json, source),
+        None => Event::MessageError(ipc::MessageError {
+            message: "The message can't be represented as JSON".to_owned(),
+            body: None
+        })
+    }
+}
+
+/// Serialize an object received from a script as JSON text. `nil`, from `undefined`, becomes `null`.
+unsafe fn to_json(object: *mut Object) -> Option<String> {
+    if object.is_null() {
+        return Some("null".to_owned());
+    }
+
+    // Only arrays and dictionaries can be serialized on their own, so wrap the object in an array.
+    // Invalid objects such as dates raise an exception, so check first.
+    let array: *mut Object = msg_send![class!(NSArray), arrayWithObject: object];
+    let valid: objc::runtime::BOOL = msg_send![class!(NSJSONSerialization), isValidJSONObject: array];
+    if valid != objc::runtime::YES {
+        return None;
+    }
+
+    let data: *const NSData = msg_send![class!(NSJSONSerialization), dataWithJSONObject: array options: 0usize error: std::ptr::null_mut::<*mut Object>()];
+    let json = String::from_utf8_lossy(data.as_ref()?.bytes());
+
+    // Unwrap the array, which is written without whitespace
+    Some(json[1..json.len() - 1].to_owned())
+}
+
+/// Convert the completion of `evaluateJavaScript:completionHandler:`
+unsafe fn script_result(result: *mut Object, error: *mut Object) -> ScriptResult {
+    // Results without a JSON form become `null`, as they do on Linux
+    if error.is_null() {
+        return Ok(to_json(result).unwrap_or_else(|| "null".to_owned()));
+    }
+
+    let domain: &NSString = msg_send![error, domain];
+    let code: isize = msg_send![error, code];
+    if domain.as_str() == "WKErrorDomain" && code == WK_ERROR_RESULT_TYPE_UNSUPPORTED {
+        return Ok("null".to_owned());
+    }
+
+    // Exceptions thrown by the script carry their details in the user info
+    let user_info: *mut Object = msg_send![error, userInfo];
+    let key = NSString::from_str("WKJavaScriptExceptionMessage");
+    let message: *const NSString = msg_send![user_info, objectForKey: key];
+    let message = match message.as_ref() {
+        Some(message) => message.as_str().to_owned(),
+        None => {
+            let description: &NSString = msg_send![error, localizedDescription];
+            description.as_str().to_owned()
+        }
+    };
+
+    let key = NSString::from_str("WKJavaScriptExceptionLineNumber");
+    let line: *mut Object = msg_send![user_info, objectForKey: key];
+    let line = if line.is_null() {
+        None
+    } else {
+        let line: u32 = msg_send![line, unsignedIntValue];
+        Some(line)
+    };
+
+    Err(ScriptError { message, line })
+}
+
+/// Convert an `NSURLRequest` into a `request::Request`
+unsafe fn convert_request(request: *mut Object) -> (request::Request, Option<BodyStream>) {
+    let method: *const NSString = msg_send![request, HTTPMethod];
+    let method = match method.as_ref() {
+        Some(method) => method.as_str().to_owned(),
+        None => "GET".to_owned()
+    };
+
+    let url: *mut Object = msg_send![request, URL];
+    let url: &NSString = msg_send![url, absoluteString];
+
+    let mut converted = request::Request::new(method, url.as_str());
+
+    let headers: *mut Object = msg_send![request, allHTTPHeaderFields];
+    if !headers.is_null() {
+        let keys: *mut Object = msg_send![headers, allKeys];
+        let count: usize = msg_send![keys, count];
+        for index in 0..count {
+            let key: &NSString = msg_send![keys, objectAtIndex: index];
+            let value: &NSString = msg_send![headers, objectForKey: key];
+            converted.headers.append(key.as_str(), value.as_str());
         }
     }
+
+    // WebKit hands larger bodies over as a stream rather than as data
+    let body: *const NSData = msg_send![request, HTTPBody];
+    if let Some(body) = body.as_ref() {
+        converted.body = body.bytes().to_vec();
+        return (converted, None);
+    }
+
+    let stream: *mut Object = msg_send![request, HTTPBodyStream];
+    if stream.is_null() {
+        return (converted, None);
+    }
+
+    let stream: *mut Object = msg_send![stream, retain];
+    (converted, Some(BodyStream(stream)))
+}
+
+/// A retained `NSInputStream` carrying a request body
+struct BodyStream(*mut Object);
+
+// The stream is only read from one thread at a time, and isn't scheduled on a run loop
+unsafe impl Send for BodyStream {}
+
+impl BodyStream {
+    /// Read the whole body on a background thread, failing if any read does
+    fn read(self, done: impl FnOnce(io::Result<Vec<u8>>) + Send + 'static) {
+        thread::spawn(move || unsafe {
+            let stream = self.0;
+            let _: () = msg_send![stream, open];
+
+            let mut body = Vec::new();
+            let mut chunk = [0u8; 8192];
+            let result = loop {
+                let len: isize = msg_send![stream, read: chunk.as_mut_ptr() maxLength: chunk.len()];
+                match len {
+                    0 => break Ok(body),
+                    len if len < 0 => break Err(io::Error::new(io::ErrorKind::Other, "Failed to read the request body")),
+                    len => body.extend_from_slice(&chunk[..len as usize])
+                }
+            };
+
+            let _: () = msg_send![stream, close];
+            let _: () = msg_send![stream, release];
+            done(result);
+        });
+    }
+}
+
+/// Run the scripts for the events emitted while the page was loading
+unsafe fn deliver_held_events(handlers: &mut Handlers, web_view: *mut Object) {
+    let scripts = handlers.event_queue.borrow_mut().finish_loading();
+    for script in scripts {
+        evaluate(web_view, &script);
+    }
+}
+
+/// Describe a navigation in progress, giving it an id if it doesn't have one yet
+unsafe fn navigation_info(handlers: &mut Handlers, web_view: *mut Object, navigation: *mut Object) -> NavigationInfo {
+    // Some loads, such as restoring a session, report no navigation object
+    let id = if navigation.is_null() {
+        navigation::next_id()
+    } else {
+        *handlers.navigations.entry(navigation as usize).or_insert_with(navigation::next_id)
+    };
+
+    let url: *mut Object = msg_send![web_view, URL];
+    NavigationInfo {
+        id,
+        url: url_string(url),
+        same_document: false
+    }
+}
+
+/// Get the text of an NSURL, which may be nil
+unsafe fn url_string(url: *mut Object) -> String {
+    if url.is_null() {
+        return String::new();
+    }
+
+    let url: *const NSString = msg_send![url, absoluteString];
+    url.as_ref().map(|url| url.as_str().to_owned()).unwrap_or_default()
+}
+
+/// Convert a WKNavigationAction for the navigation policy
+unsafe fn convert_navigation_action(action: *mut Object, url: *mut Object) -> NavigationAction {
+    let url = url_string(url);
+
+    let navigation_type: isize = msg_send![action, navigationType];
+    let navigation_type = match navigation_type {
+        0 => NavigationType::LinkClicked,
+        1 => NavigationType::FormSubmitted,
+        2 => NavigationType::BackForward,
+        3 => NavigationType::Reload,
+        4 => NavigationType::FormResubmitted,
+        _ => NavigationType::Other
+    };
+
+    // Navigations opening a new window have no target frame yet, but will load a top-level document
+    let frame: *mut Object = msg_send![action, targetFrame];
+    let main_frame = frame.is_null() || {
+        let main_frame: objc::runtime::BOOL = msg_send![frame, isMainFrame];
+        main_frame == objc::runtime::YES
+    };
+
+    NavigationAction {
+        url,
+        navigation_type,
+        main_frame: Some(main_frame)
+    }
+}
+
+/// Get the frame a script message was sent from
+unsafe fn message_source(message: *mut Object) -> Option<MessageSource> {
+    let frame: *mut Object = msg_send![message, frameInfo];
+    if frame.is_null() {
+        return None;
+    }
+
+    let main_frame: objc::runtime::BOOL = msg_send![frame, isMainFrame];
+
+    let request: *mut Object = msg_send![frame, request];
+    let url: *mut Object = msg_send![request, URL];
+    let url = url_string(url);
+
+    let origin: *mut Object = msg_send![frame, securityOrigin];
+    let protocol: *const NSString = msg_send![origin, protocol];
+    let host: *const NSString = msg_send![origin, host];
+    let port: isize = msg_send![origin, port];
+
+    let origin = match (protocol.as_ref(), host.as_ref()) {
+        (Some(protocol), Some(host)) if protocol.len() > 0 && port > 0 => {
+            format!("{}://{}:{}", protocol.as_str(), host.as_str(), port)
+        }
+        (Some(protocol), Some(host)) if protocol.len() > 0 => format!("{}://{}", protocol.as_str(), host.as_str()),
+        // Opaque origins, e.g. of sandboxed iframes, serialize as `null`
+        _ => "null".to_owned()
+    };
+
+    #[cfg(feature = "macos-private-api")]
+    let frame_id = frame_id(frame);
+    #[cfg(not(feature = "macos-private-api"))]
+    let frame_id = None;
+
+    Some(MessageSource {
+        url,
+        origin,
+        main_frame: Some(main_frame == objc::runtime::YES),
+        frame_id
+    })
+}
+
+/// Get the id of a frame. WKFrameInfo only exposes it through its private handle.
+#[cfg(feature = "macos-private-api")]
+unsafe fn frame_id(frame: *mut Object) -> Option<u64> {
+    let responds: objc::runtime::BOOL = msg_send![frame, respondsToSelector: sel!(_handle)];
+    if responds != objc::runtime::YES {
+        return None;
+    }
+
+    let handle: *mut Object = msg_send![frame, _handle];
+    if handle.is_null() {
+        return None;
+    }
+
+    let responds: objc::runtime::BOOL = msg_send![handle, respondsToSelector: sel!(frameID)];
+    if responds != objc::runtime::YES {
+        return None;
+    }
+
+    let id: u64 = msg_send![handle, frameID];
+    Some(id)
 }
 
-impl<T: EventHandler, K: request::RequestHandler> WinitDelegate<T, K> {
-    pub fn new(event_handler: T, request_handler: K) -> *mut Object {
+impl<T: EventHandler> WinitDelegate<T> {
+    fn new(event_handler: T, handlers: Handlers) -> *mut Object {
         let event_handler = Box::new(event_handler);
-        let request_handler = Box::new(request_handler);
+        let handlers = Box::new(handlers);
 
         unsafe {
             let del: *mut Object = msg_send![Self::class(), alloc];
-            let del: *mut Object = msg_send![del, initWithHandler: Box::into_raw(event_handler) as *mut c_void requestHandler: Box::into_raw(request_handler) as *mut c_void];
+            let del: *mut Object = msg_send![del, initWithHandler: Box::into_raw(event_handler) as *mut c_void handlers: Box::into_raw(handlers) as *mut c_void];
 
             del
         }
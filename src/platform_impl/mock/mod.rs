@@ -0,0 +1,249 @@
+use crate::*;
+use platform::mock::Navigation;
+use std::{
+    cell::RefCell,
+    collections::{HashMap, VecDeque},
+    rc::Rc,
+    sync::mpsc,
+};
+use winit::window::Window;
+
+#[derive(Debug, Default)]
+pub struct PlatformWebViewBuilder {
+    pub(crate) title: Option<String>,
+}
+
+impl PlatformWebViewBuilder {
+    pub fn new() -> Self {
+        Default::default()
+    }
+}
+
+#[derive(Debug, Clone)]
+pub enum PlatformNavigationEvent {
+    /// Triggered when a redirect has taken place, with the URL redirected to
+    Redirect(NavigationInfo),
+}
+
+#[derive(Debug, Clone, Copy)]
+pub enum PlatformEvent {}
+
+pub struct PlatformWebView {
+    events: Rc<dyn Fn(Event)>,
+    request_handlers: HashMap<String, request::BoxedHandler>,
+    #[cfg(feature = "serde")]
+    commands: ipc::Commands,
+    pub(crate) base_url: String,
+    pub(crate) init_scripts: Vec<String>,
+    channels: Vec<String>,
+    navigation_policy: Option<navigation::PolicyHandler>,
+    pub(crate) navigations: Vec<Navigation>,
+    /// The navigation that has started but not yet finished or failed
+    loading: Option<NavigationInfo>,
+    pub(crate) evaluated_scripts: Vec<String>,
+    /// The completions of scripts run with `evaluate_with_result`, oldest first
+    pending_scripts: VecDeque<script::ScriptCallback>,
+    event_queue: script::EventQueue,
+    pub(crate) outbox: binary::Outbox,
+    pub(crate) title: Option<String>,
+}
+
+impl PlatformWebView {
+    pub fn build(
+        builder: WebViewBuilder<impl request::RequestHandler>,
+        event_handler: impl EventHandler,
+        _window: &mut Window,
+    ) -> Self {
+        Self::build_headless(builder, event_handler)
+    }
+
+    pub fn build_headless(
+        builder: WebViewBuilder<impl request::RequestHandler>,
+        event_handler: impl EventHandler,
+    ) -> Self {
+        let WebViewBuilder {
+            init_scripts,
+            channels,
+            navigation_policy,
+            request_handler,
+            mut schemes,
+            base_url,
+            #[cfg(feature = "serde")]
+            commands,
+            platform,
+        } = builder;
+
+        let events: Rc<dyn Fn(Event)> = {
+            let event_handler = RefCell::new(event_handler);
+            Rc::new(move |event| event_handler.borrow_mut().handle_event(event))
+        };
+
+        let outbox = binary::Outbox::default();
+        let binary_handler = binary::BinaryHandler::new(events.clone(), outbox.clone(), schemes.keys(), &base_url);
+        schemes.insert(binary::SCHEME.to_owned(), request::boxed(binary_handler));
+
+        PlatformWebView {
+            events,
+            request_handlers: request::scheme_handlers(request_handler, schemes),
+            #[cfg(feature = "serde")]
+            commands,
+            base_url,
+            init_scripts,
+            channels,
+            navigation_policy,
+            navigations: Vec::new(),
+            loading: None,
+            evaluated_scripts: Vec::new(),
+            pending_scripts: VecDeque::new(),
+            event_queue: script::EventQueue::default(),
+            outbox,
+            title: platform.title,
+        }
+    }
+
+    pub fn navigate(&mut self, target: NavigationTarget) {
+        self.navigations.push(match target {
+            NavigationTarget::Url(url) => Navigation::Url(url.to_owned()),
+            NavigationTarget::Html(html) => Navigation::Html(html.to_owned()),
+        });
+    }
+
+    pub fn execute(&mut self, js: impl AsRef<str>) {
+        self.evaluated_scripts.push(js.as_ref().to_owned());
+    }
+
+    pub fn evaluate_with_result(&mut self, js: impl AsRef<str>, callback: script::ScriptCallback) {
+        self.execute(js);
+        self.pending_scripts.push_back(callback);
+    }
+
+    pub(crate) fn event_sink(&self) -> Rc<dyn Fn(Event)> {
+        self.events.clone()
+    }
+
+    /// Complete the oldest script still waiting for its result
+    pub(crate) fn complete_script(&mut self, result: ScriptResult) -> bool {
+        match self.pending_scripts.pop_front() {
+            Some(callback) => {
+                callback(result);
+                true
+            }
+            None => false,
+        }
+    }
+
+    pub fn title(&self) -> Option<String> {
+        self.title.clone()
+    }
+
+    pub(crate) fn emit(&mut self, script: String) {
+        if let Some(script) = self.event_queue.push(script) {
+            self.execute(script);
+        }
+    }
+
+    pub(crate) fn dispatch(&mut self, event: Event) {
+        // Track loading like the engines do, so emitted events are held until the page loads
+        match &event {
+            Event::Navigation(NavigationEvent::Start(navigation)) if !navigation.same_document => {
+                let leaving_page = self.event_queue.start_loading();
+                if leaving_page {
+                    self.outbox.clear();
+                }
+            }
+            Event::Navigation(NavigationEvent::Finish(navigation)) if !navigation.same_document => {
+                for script in self.event_queue.finish_loading() {
+                    self.execute(script);
+                }
+            }
+            _ => {}
+        }
+
+        (self.events)(event)
+    }
+
+    /// Deliver the Start event of a navigation
+    pub(crate) fn start_navigation(&mut self, navigation: NavigationInfo) {
+        self.loading = Some(navigation.clone());
+        self.dispatch(Event::Navigation(NavigationEvent::Start(navigation)));
+    }
+
+    /// Deliver the Commit and Finish events of the navigation that has started,
+    /// or the Start, Commit and Finish events of a new one
+    pub(crate) fn complete_navigation(&mut self, navigation: NavigationInfo) {
+        let navigation = match self.loading.take() {
+            Some(loading) => loading,
+            None => {
+                self.dispatch(Event::Navigation(NavigationEvent::Start(navigation.clone())));
+                navigation
+            }
+        };
+
+        self.dispatch(Event::Navigation(NavigationEvent::Commit(navigation.clone())));
+        self.dispatch(Event::Navigation(NavigationEvent::Finish(navigation)));
+    }
+
+    /// End the navigation that has started without finishing it, delivering the events held while it loaded
+    pub(crate) fn fail_navigation(&mut self) {
+        if self.loading.take().is_some() {
+            for script in self.event_queue.finish_loading() {
+                self.execute(script);
+            }
+        }
+    }
+
+    /// Deliver the events of a navigation within the document
+    pub(crate) fn navigate_within_document(&mut self, navigation: NavigationInfo) {
+        self.dispatch(Event::Navigation(NavigationEvent::Start(navigation.clone())));
+        self.dispatch(Event::Navigation(NavigationEvent::Commit(navigation.clone())));
+        self.dispatch(Event::Navigation(NavigationEvent::Finish(navigation)));
+    }
+
+    /// Ask the navigation policy whether a navigation may go ahead
+    pub(crate) fn decide_policy(&mut self, action: &NavigationAction) -> NavigationPolicy {
+        match &mut self.navigation_policy {
+            Some(policy) => policy(action),
+            None => NavigationPolicy::Allow,
+        }
+    }
+
+    /// Deliver a message posted to a channel, if the channel is registered
+    pub(crate) fn post_to_channel(&mut self, channel: &str, message: &str) -> bool {
+        if !self.channels.iter().any(|name| name == channel) {
+            return false;
+        }
+
+        self.dispatch(Event::ChannelMessage {
+            channel: channel.to_owned(),
+            message: message.to_owned(),
+            source: None,
+        });
+        true
+    }
+
+    /// Issue a request and wait for the handler to respond
+    pub(crate) fn request(&mut self, request: request::Request) -> Option<request::Response<request::BoxedBody>> {
+        let (sender, receiver) = mpsc::channel();
+        let responder = request::Responder::new(request.cancellation.clone(), move |response| {
+            sender.send(response).ok();
+        });
+
+        // Like the real engines, requests to unregistered schemes fail
+        match self.request_handlers.get_mut(&request.scheme().to_ascii_lowercase()) {
+            Some(request_handler) => request_handler(request, responder),
+            None => responder.respond(None),
+        }
+        receiver.recv().ok().flatten()
+    }
+
+    /// Call a command and wait for it to reply
+    #[cfg(feature = "serde")]
+    pub(crate) fn invoke(&mut self, name: &str, args: serde_json::Value) -> Result<serde_json::Value, String> {
+        let (sender, receiver) = mpsc::channel();
+        ipc::call_command(&mut self.commands, name, args, None, move |result| {
+            sender.send(result).ok();
+        });
+
+        receiver.recv().unwrap_or_else(|_| Err("The command didn't reply".to_owned()))
+    }
+}
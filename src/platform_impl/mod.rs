@@ -1,5 +1,30 @@
-#[cfg(target_os = "macos")]
+/// Schemes WebKitGTK loads itself, which can't be given a handler
+#[cfg(not(all(target_os = "macos", not(feature = "mock"))))]
+const BUILT_IN_SCHEMES: [&str; 10] = ["about", "blob", "data", "file", "ftp", "http", "https", "javascript", "ws", "wss"];
+
+/// Whether the engine loads a scheme itself
+#[cfg(not(all(target_os = "macos", not(feature = "mock"))))]
+pub(crate) fn handles_scheme(scheme: &str) -> bool {
+    BUILT_IN_SCHEMES.contains(&scheme)
+}
+
+#[cfg(feature = "mock")]
+mod mock;
+
+#[cfg(feature = "mock")]
+pub use mock::*;
+
+#[cfg(all(target_os = "macos", not(feature = "mock")))]
 mod macos;
 
-#[cfg(target_os = "macos")]
+#[cfg(all(target_os = "macos", not(feature = "mock")))]
 pub use macos::*;
+
+#[cfg(all(target_os = "linux", not(feature = "mock")))]
+mod linux;
+
+#[cfg(all(target_os = "linux", not(feature = "mock")))]
+pub use linux::*;
+
+#[cfg(all(target_os = "linux", not(any(feature = "mock", feature = "webkitgtk"))))]
+compile_error!("winit-webview needs the `webkitgtk` feature on Linux, or `mock` to build without a webview");
@@ -0,0 +1,408 @@
+use std::{
+    collections::HashMap,
+    fs,
+    io::{self, Read, Seek, SeekFrom},
+    path::{Path, PathBuf}
+};
+
+use flate2::read::{DeflateDecoder, GzDecoder};
+
+use super::{
+    static_dir::{mime_type, resolve_virtual, safe_segments, serve_file, serve_stream},
+    BoxedBody, Request, RequestHandler, Response
+};
+
+/// Serves files from a zip, tar or gzip-compressed tar archive.
+///
+/// Only the archive's table of contents is read up front. Each request reopens the archive
+/// and streams just the requested entry, so large bundles are never loaded into memory.
+/// Paths resolve like `StaticDir`, and uncompressed entries of zip and plain tar archives
+/// answer `Range` requests.
+pub struct Archive {
+    path: PathBuf,
+    format: Format,
+    entries: HashMap<String, Entry>,
+    index: String,
+    fallback: Option<String>
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Format {
+    Zip,
+    Tar,
+    TarGz
+}
+
+#[derive(Debug)]
+struct Entry {
+    /// The offset of the entry's data, within the decompressed stream for `TarGz`
+    offset: u64,
+    /// The size of the data stored in the archive
+    stored_size: u64,
+    size: u64,
+    compression: Compression
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Compression {
+    Stored,
+    Deflated,
+    Unsupported
+}
+
+impl Archive {
+    /// Read the table of contents of an archive. The format is detected from its contents.
+    pub fn open(path: impl Into<PathBuf>) -> io::Result<Self> {
+        let path = path.into();
+        let mut file = fs::File::open(&path)?;
+
+        let mut magic = Vec::with_capacity(4);
+        (&mut file).take(4).read_to_end(&mut magic)?;
+        file.seek(SeekFrom::Start(0))?;
+
+        let (format, entries) = match magic.as_slice() {
+            [b'P', b'K', 3, 4] | [b'P', b'K', 5, 6] => (Format::Zip, zip_entries(file)?),
+            [0x1f, 0x8b, ..] => (Format::TarGz, tar_entries(GzDecoder::new(file))?),
+            _ => (Format::Tar, tar_entries(file)?)
+        };
+
+        Ok(Self {
+            path,
+            format,
+            entries,
+            index: "index.html".to_owned(),
+            fallback: None
+        })
+    }
+
+    /// Set the file served for directories. Defaults to `index.html`.
+    pub fn with_index(mut self, index: impl Into<String>) -> Self {
+        self.index = index.into();
+        self
+    }
+
+    /// Serve the specified file for missing paths without a file extension
+    pub fn with_spa_fallback(mut self, entry_point: impl Into<String>) -> Self {
+        self.fallback = Some(entry_point.into());
+        self
+    }
+
+    /// Get the paths of the files in the archive
+    pub fn paths(&self) -> impl Iterator<Item = &str> {
+        self.entries.keys().map(String::as_str)
+    }
+
+    /// Open a reader over the decompressed contents of an entry
+    fn read_entry(&self, entry: &Entry) -> io::Result<BoxedBody> {
+        if self.format == Format::TarGz {
+            // Gzip streams can't seek, so decompress up to the entry as the body is first read
+            let reader = Skip {
+                inner: GzDecoder::new(fs::File::open(&self.path)?),
+                skip: entry.offset
+            };
+
+            return Ok(Box::new(reader.take(entry.size)));
+        }
+
+        let data = Slice::open(&self.path, entry.offset, entry.stored_size)?;
+        match entry.compression {
+            Compression::Stored => Ok(Box::new(data)),
+            Compression::Deflated => Ok(Box::new(DeflateDecoder::new(data))),
+            Compression::Unsupported => Err(io::Error::new(io::ErrorKind::InvalidData, "Unsupported compression method"))
+        }
+    }
+}
+
+impl RequestHandler for Archive {
+    type Read = BoxedBody;
+
+    fn handle(&mut self, request: Request) -> Option<Response<Self::Read>> {
+        let entries = &self.entries;
+        let exists = |path: &str| entries.contains_key(path);
+        let found = resolve_virtual(&request.relative_path(), &self.index, self.fallback.as_deref(), exists)
+            .map(|path| (&self.entries[&path], mime_type(&path)));
+
+        let response = match found {
+            // Uncompressed entries can seek, so they can answer range requests
+            Ok((entry, mime_type)) if self.format != Format::TarGz && entry.compression == Compression::Stored => {
+                serve_file(&request, || {
+                    let data = Slice::open(&self.path, entry.offset, entry.stored_size).map_err(|_| 500u16)?;
+                    Ok(Response::ok(data, mime_type))
+                })
+            }
+            Ok((entry, mime_type)) => serve_stream(&request, || {
+                let body = self.read_entry(entry).map_err(|_| 500u16)?;
+                Ok(Response::ok(body, mime_type).with_content_length(entry.size))
+            }),
+            Err(status) => serve_stream(&request, || Err::<Response<io::Empty>, _>(status))
+        };
+
+        Some(response)
+    }
+}
+
+/// Map the normalized path of each file in a zip archive to where its data is stored
+fn zip_entries(file: fs::File) -> io::Result<HashMap<String, Entry>> {
+    let mut archive = zip::ZipArchive::new(file)?;
+    let mut entries = HashMap::new();
+
+    for index in 0..archive.len() {
+        // Raw access finds the data without setting up decompression
+        let file = archive.by_index_raw(index)?;
+        if file.is_dir() {
+            continue;
+        }
+
+        let path = match normalize(file.name()) {
+            Some(path) => path,
+            None => continue
+        };
+
+        let compression = match file.compression() {
+            zip::CompressionMethod::Stored => Compression::Stored,
+            zip::CompressionMethod::Deflated => Compression::Deflated,
+            _ => Compression::Unsupported
+        };
+
+        entries.insert(
+            path,
+            Entry {
+                offset: file.data_start(),
+                stored_size: file.compressed_size(),
+                size: file.size(),
+                compression
+            }
+        );
+    }
+
+    Ok(entries)
+}
+
+/// Map the normalized path of each file in a tar archive to where its data is stored
+fn tar_entries(reader: impl Read) -> io::Result<HashMap<String, Entry>> {
+    let mut archive = tar::Archive::new(reader);
+    let mut entries = HashMap::new();
+
+    for entry in archive.entries()? {
+        let entry = entry?;
+        if !entry.header().entry_type().is_file() {
+            continue;
+        }
+
+        let path = match normalize(&entry.path()?.to_string_lossy()) {
+            Some(path) => path,
+            None => continue
+        };
+
+        entries.insert(
+            path,
+            Entry {
+                offset: entry.raw_file_position(),
+                stored_size: entry.size(),
+                size: entry.size(),
+                compression: Compression::Stored
+            }
+        );
+    }
+
+    Ok(entries)
+}
+
+/// Normalize an entry name to a `/`-separated relative path, skipping names that would escape the root
+fn normalize(name: &str) -> Option<String> {
+    let path = safe_segments(&name.replace('\\', "/"))?.join("/");
+    Some(path).filter(|path| !path.is_empty())
+}
+
+/// A seekable window onto part of a file
+struct Slice {
+    file: fs::File,
+    start: u64,
+    len: u64,
+    pos: u64
+}
+
+impl Slice {
+    fn open(path: &Path, start: u64, len: u64) -> io::Result<Self> {
+        let mut file = fs::File::open(path)?;
+        file.seek(SeekFrom::Start(start))?;
+
+        Ok(Self { file, start, len, pos: 0 })
+    }
+}
+
+impl Read for Slice {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let remaining = self.len.saturating_sub(self.pos);
+        let max = remaining.min(buf.len() as u64) as usize;
+        if max == 0 {
+            return Ok(0);
+        }
+
+        let len = self.file.read(&mut buf[..max])?;
+        self.pos += len as u64;
+        Ok(len)
+    }
+}
+
+impl Seek for Slice {
+    fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
+        let pos = match pos {
+            SeekFrom::Start(pos) => Some(pos),
+            SeekFrom::End(offset) => self.len.checked_add_signed(offset),
+            SeekFrom::Current(offset) => self.pos.checked_add_signed(offset)
+        };
+
+        let pos = pos.ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "Seek before the start of the entry"))?;
+        self.file.seek(SeekFrom::Start(self.start + pos))?;
+        self.pos = pos;
+        Ok(pos)
+    }
+}
+
+/// A reader that discards the start of another reader the first time it's read
+struct Skip<R> {
+    inner: R,
+    skip: u64
+}
+
+impl<R: Read> Read for Skip<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if self.skip > 0 {
+            let skipped = io::copy(&mut (&mut self.inner).take(self.skip), &mut io::sink())?;
+            if skipped < self.skip {
+                return Err(io::ErrorKind::UnexpectedEof.into());
+            }
+
+            self.skip = 0;
+        }
+
+        self.inner.read(buf)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        env,
+        io::Write,
+        process,
+        sync::atomic::{AtomicUsize, Ordering}
+    };
+
+    use flate2::{write::GzEncoder, Compression as Level};
+    use zip::{write::FileOptions, CompressionMethod, ZipWriter};
+
+    use super::*;
+
+    /// Get a path for an archive, unique to the test
+    fn archive_path(name: &str, extension: &str) -> PathBuf {
+        static NEXT: AtomicUsize = AtomicUsize::new(0);
+        let unique = NEXT.fetch_add(1, Ordering::Relaxed);
+        env::temp_dir().join(format!("winit-webview-{}-{}-{}.{}", name, process::id(), unique, extension))
+    }
+
+    fn write_tar(writer: impl Write, files: &[(&str, &str)]) -> impl Write {
+        let mut builder = tar::Builder::new(writer);
+        for (name, contents) in files {
+            let mut header = tar::Header::new_gnu();
+            header.set_size(contents.len() as u64);
+            header.set_mode(0o644);
+            header.set_cksum();
+            builder.append_data(&mut header, name, contents.as_bytes()).unwrap();
+        }
+
+        builder.into_inner().unwrap()
+    }
+
+    fn tar_archive(name: &str, files: &[(&str, &str)]) -> PathBuf {
+        let path = archive_path(name, "tar");
+        write_tar(fs::File::create(&path).unwrap(), files);
+        path
+    }
+
+    fn tar_gz_archive(name: &str, files: &[(&str, &str)]) -> PathBuf {
+        let path = archive_path(name, "tar.gz");
+        let encoder = GzEncoder::new(fs::File::create(&path).unwrap(), Level::default());
+        drop(write_tar(encoder, files));
+        path
+    }
+
+    fn zip_archive(name: &str, method: CompressionMethod, files: &[(&str, &str)]) -> PathBuf {
+        let path = archive_path(name, "zip");
+        let mut writer = ZipWriter::new(fs::File::create(&path).unwrap());
+        for (name, contents) in files {
+            writer.start_file(*name, FileOptions::default().compression_method(method)).unwrap();
+            writer.write_all(contents.as_bytes()).unwrap();
+        }
+
+        writer.finish().unwrap();
+        path
+    }
+
+    /// Make a request, returning the status and body
+    fn send(archive: &mut Archive, request: Request) -> (u16, String) {
+        let mut response = archive.handle(request).unwrap();
+        let mut body = String::new();
+        response.body.read_to_string(&mut body).unwrap();
+        (response.status, body)
+    }
+
+    fn get(archive: &mut Archive, url: &str) -> (u16, String) {
+        send(archive, Request::get(url))
+    }
+
+    const FILES: &[(&str, &str)] = &[("index.html", "<p>Home</p>"), ("docs/index.html", "<p>Docs</p>"), ("app.js", "run()")];
+
+    #[test]
+    fn serves_every_format() {
+        let archives = [
+            tar_archive("formats", FILES),
+            tar_gz_archive("formats", FILES),
+            zip_archive("formats", CompressionMethod::Stored, FILES),
+            zip_archive("formats", CompressionMethod::Deflated, FILES)
+        ];
+
+        for path in archives {
+            let mut archive = Archive::open(&path).unwrap();
+            let mut paths: Vec<_> = archive.paths().collect();
+            paths.sort_unstable();
+            assert_eq!(paths, ["app.js", "docs/index.html", "index.html"], "{}", path.display());
+
+            assert_eq!(get(&mut archive, "winit:///app.js"), (200, "run()".to_owned()));
+            assert_eq!(get(&mut archive, "winit:///"), (200, "<p>Home</p>".to_owned()));
+            assert_eq!(get(&mut archive, "winit:///docs/"), (200, "<p>Docs</p>".to_owned()));
+            assert_eq!(get(&mut archive, "winit:///missing.js").0, 404);
+            assert_eq!(get(&mut archive, "winit:///../app.js").0, 403);
+        }
+    }
+
+    #[test]
+    fn falls_back_to_the_entry_point() {
+        let path = zip_archive("fallback", CompressionMethod::Deflated, FILES);
+        let mut archive = Archive::open(path).unwrap().with_spa_fallback("index.html");
+
+        assert_eq!(get(&mut archive, "winit:///settings/profile"), (200, "<p>Home</p>".to_owned()));
+        assert_eq!(get(&mut archive, "winit:///missing.js").0, 404);
+    }
+
+    #[test]
+    fn answers_ranges_from_stored_entries() {
+        let files = &[("first.txt", "first"), ("media.bin", "0123456789")];
+        let range = |url| Request::get(url).with_header("Range", "bytes=2-4");
+
+        for path in [tar_archive("ranges", files), zip_archive("ranges", CompressionMethod::Stored, files)] {
+            let mut archive = Archive::open(path).unwrap();
+            assert_eq!(send(&mut archive, range("winit:///media.bin")), (206, "234".to_owned()));
+
+            let response = archive.handle(range("winit:///media.bin")).unwrap();
+            assert_eq!(response.headers.get("Content-Range"), Some("bytes 2-4/10"));
+        }
+
+        // Compressed entries can't seek, so they're always sent whole
+        for path in [tar_gz_archive("ranges", files), zip_archive("ranges", CompressionMethod::Deflated, files)] {
+            let mut archive = Archive::open(path).unwrap();
+            assert_eq!(send(&mut archive, range("winit:///media.bin")), (200, "0123456789".to_owned()));
+        }
+    }
+}
@@ -0,0 +1,65 @@
+use std::{
+    fmt,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, Mutex,
+    },
+};
+
+type Callback = Box<dyn FnOnce() + Send>;
+
+/// Signals that the page no longer wants the response to a request,
+/// e.g. because a `fetch` was aborted or the page navigated away.
+#[derive(Clone, Default)]
+pub struct CancellationToken {
+    inner: Arc<Inner>
+}
+
+#[derive(Default)]
+struct Inner {
+    cancelled: AtomicBool,
+    callbacks: Mutex<Vec<Callback>>
+}
+
+impl CancellationToken {
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.inner.cancelled.load(Ordering::Acquire)
+    }
+
+    /// Run a callback once the request is cancelled, or immediately if it already has been
+    pub fn on_cancel(&self, callback: impl FnOnce() + Send + 'static) {
+        let mut callbacks = self.inner.callbacks.lock().unwrap();
+        if self.is_cancelled() {
+            drop(callbacks);
+            callback();
+        } else {
+            callbacks.push(Box::new(callback));
+        }
+    }
+
+    /// Cancel the request, running any registered callbacks
+    pub fn cancel(&self) {
+        let callbacks = {
+            let mut callbacks = self.inner.callbacks.lock().unwrap();
+            if self.inner.cancelled.swap(true, Ordering::AcqRel) {
+                return;
+            }
+
+            std::mem::take(&mut *callbacks)
+        };
+
+        for callback in callbacks {
+            callback();
+        }
+    }
+}
+
+impl fmt::Debug for CancellationToken {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("CancellationToken").field("cancelled", &self.is_cancelled()).finish()
+    }
+}
@@ -0,0 +1,300 @@
+use std::{
+    convert::TryFrom,
+    io,
+    time::{Duration, SystemTime, UNIX_EPOCH}
+};
+
+use super::{BoxedBody, BoxedHandler, Request, Responder, Response};
+
+impl<T: io::Read> Response<T> {
+    /// Set the `ETag` validator. Unquoted tags are quoted.
+    pub fn with_etag(mut self, etag: impl Into<String>) -> Self {
+        let etag = etag.into();
+        let etag = if etag.starts_with('"') || etag.starts_with("W/\"") { etag } else { format!("\"{}\"", etag) };
+
+        self.headers.insert("ETag", etag);
+        self
+    }
+
+    /// Set the `Last-Modified` validator
+    pub fn with_last_modified(mut self, time: SystemTime) -> Self {
+        self.headers.insert("Last-Modified", format_http_date(time));
+        self
+    }
+
+    pub fn etag(&self) -> Option<&str> {
+        self.headers.get("ETag")
+    }
+
+    pub fn last_modified(&self) -> Option<SystemTime> {
+        parse_http_date(self.headers.get("Last-Modified")?)
+    }
+
+    /// Check an `If-Range` header against the validators, which must match exactly
+    pub(crate) fn satisfies_if_range(&self, value: &str) -> bool {
+        let value = value.trim();
+        if value.starts_with('"') {
+            return self.etag() == Some(value);
+        }
+
+        match (parse_http_date(value), self.last_modified()) {
+            (Some(since), Some(modified)) => modified == since,
+            _ => false
+        }
+    }
+}
+
+/// The validators a request carries to check whether its cached copy is still current
+struct Conditions {
+    if_none_match: Option<String>,
+    if_modified_since: Option<SystemTime>
+}
+
+impl Conditions {
+    fn from_request(request: &Request) -> Option<Self> {
+        if !matches!(request.method.as_str(), "GET" | "HEAD") {
+            return None;
+        }
+
+        let conditions = Conditions {
+            if_none_match: request.header("If-None-Match").map(str::to_owned),
+            if_modified_since: request.header("If-Modified-Since").and_then(parse_http_date)
+        };
+
+        if conditions.if_none_match.is_none() && conditions.if_modified_since.is_none() {
+            None
+        } else {
+            Some(conditions)
+        }
+    }
+
+    /// Check whether the cached copy matches the response
+    fn is_fresh<T: io::Read>(&self, response: &Response<T>) -> bool {
+        if !(200..300).contains(&response.status) {
+            return false;
+        }
+
+        // `If-Modified-Since` only applies when there's no `If-None-Match`
+        if let Some(tags) = &self.if_none_match {
+            return match response.etag() {
+                Some(etag) => tags.split(',').any(|tag| tag.trim() == "*" || weak_eq(tag.trim(), etag)),
+                None => tags.trim() == "*"
+            };
+        }
+
+        match (self.if_modified_since, response.last_modified()) {
+            (Some(since), Some(modified)) => modified <= since,
+            _ => false
+        }
+    }
+}
+
+/// Answer conditional requests with `304 Not Modified` when the handler's response
+/// carries matching validators
+pub(crate) fn conditional(mut handler: BoxedHandler) -> BoxedHandler {
+    Box::new(move |request: Request, responder: Responder<BoxedBody>| {
+        let conditions = match Conditions::from_request(&request) {
+            Some(conditions) => conditions,
+            None => return handler(request, responder)
+        };
+
+        handler(
+            request,
+            responder.map(move |mut response: Response<BoxedBody>| {
+                if conditions.is_fresh(&response) {
+                    response.status = 304;
+                    response.headers.remove("Content-Length");
+                    response.headers.remove("Content-Range");
+                    response.body = Box::new(io::empty());
+                }
+
+                response
+            })
+        )
+    })
+}
+
+/// Compare entity tags, ignoring whether they're weak
+fn weak_eq(a: &str, b: &str) -> bool {
+    a.trim_start_matches("W/") == b.trim_start_matches("W/")
+}
+
+const WEEKDAYS: [&str; 7] = ["Sun", "Mon", "Tue", "Wed", "Thu", "Fri", "Sat"];
+const MONTHS: [&str; 12] = ["Jan", "Feb", "Mar", "Apr", "May", "Jun", "Jul", "Aug", "Sep", "Oct", "Nov", "Dec"];
+
+/// Format a time as an HTTP date, e.g. `Sun, 06 Nov 1994 08:49:37 GMT`
+fn format_http_date(time: SystemTime) -> String {
+    let secs = time.duration_since(UNIX_EPOCH).map(|time| time.as_secs()).unwrap_or(0);
+    let (days, secs) = (secs / 86400, secs % 86400);
+    let (year, month, day) = civil_from_days(days as i64);
+
+    format!(
+        "{}, {:02} {} {} {:02}:{:02}:{:02} GMT",
+        WEEKDAYS[((days + 4) % 7) as usize],
+        day,
+        MONTHS[month as usize - 1],
+        year,
+        secs / 3600,
+        secs % 3600 / 60,
+        secs % 60
+    )
+}
+
+/// Parse an HTTP date in the preferred `Sun, 06 Nov 1994 08:49:37 GMT` format
+fn parse_http_date(date: &str) -> Option<SystemTime> {
+    let mut parts = date.split_whitespace().skip(1);
+    let day: i64 = parts.next()?.parse().ok()?;
+    let month = parts.next()?;
+    let month = MONTHS.iter().position(|name| *name == month)? as i64 + 1;
+    let year: i64 = parts.next()?.parse().ok()?;
+
+    let mut time = parts.next()?.split(':').map(|part| part.parse::<u64>().ok());
+    let (hours, minutes, seconds) = (time.next()??, time.next()??, time.next()??);
+    if parts.next() != Some("GMT") || !(1..=31).contains(&day) || hours > 23 || minutes > 59 || seconds > 60 {
+        return None;
+    }
+
+    let days = u64::try_from(days_from_civil(year, month, day)).ok()?;
+    Some(UNIX_EPOCH + Duration::from_secs(days * 86400 + hours * 3600 + minutes * 60 + seconds))
+}
+
+/// Convert days since the Unix epoch to a (year, month, day) date
+fn civil_from_days(days: i64) -> (i64, i64, i64) {
+    let days = days + 719_468;
+    let era = days.div_euclid(146_097);
+    let day_of_era = days.rem_euclid(146_097);
+    let year_of_era = (day_of_era - day_of_era / 1460 + day_of_era / 36524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let month = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * month + 2) / 5 + 1;
+    let month = if month < 10 { month + 3 } else { month - 9 };
+
+    (year_of_era + era * 400 + (month <= 2) as i64, month, day)
+}
+
+/// Convert a (year, month, day) date to days since the Unix epoch
+fn days_from_civil(year: i64, month: i64, day: i64) -> i64 {
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = year.div_euclid(400);
+    let year_of_era = year.rem_euclid(400);
+    let day_of_year = (153 * ((month + 9) % 12) + 2) / 5 + day - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+
+    era * 146_097 + day_of_era - 719_468
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::mpsc;
+
+    use super::*;
+    use crate::request::{boxed, CancellationToken};
+
+    fn date(secs: u64) -> SystemTime {
+        UNIX_EPOCH + Duration::from_secs(secs)
+    }
+
+    fn resource(request: Request) -> Option<Response<io::Cursor<Vec<u8>>>> {
+        let response = Response::bytes("contents", "text/plain").with_last_modified(date(784_111_777));
+        match request.query_param("etag") {
+            Some(etag) => Some(response.with_etag(etag)),
+            None => Some(response)
+        }
+    }
+
+    fn send(request: Request) -> Response<BoxedBody> {
+        let (sender, receiver) = mpsc::channel();
+        let mut handler = conditional(boxed(resource));
+        handler(request, Responder::new(CancellationToken::new(), move |response| {
+            sender.send(response).unwrap();
+        }));
+
+        receiver.recv().unwrap().unwrap()
+    }
+
+    fn fresh(if_none_match: &str, etag: &str) -> bool {
+        let conditions = Conditions { if_none_match: Some(if_none_match.to_owned()), if_modified_since: None };
+        conditions.is_fresh(&Response::bytes("", "text/plain").with_etag(etag))
+    }
+
+    #[test]
+    fn formats_http_dates() {
+        assert_eq!(format_http_date(date(784_111_777)), "Sun, 06 Nov 1994 08:49:37 GMT");
+        assert_eq!(format_http_date(UNIX_EPOCH), "Thu, 01 Jan 1970 00:00:00 GMT");
+        assert_eq!(format_http_date(date(951_782_400)), "Tue, 29 Feb 2000 00:00:00 GMT");
+    }
+
+    #[test]
+    fn round_trips_http_dates() {
+        for secs in [0, 784_111_777, 951_782_400, 1_700_000_000, 4_102_444_799] {
+            assert_eq!(parse_http_date(&format_http_date(date(secs))), Some(date(secs)));
+        }
+
+        // Sub-second precision is lost
+        let time = date(1_700_000_000) + Duration::from_millis(500);
+        assert_eq!(parse_http_date(&format_http_date(time)), Some(date(1_700_000_000)));
+    }
+
+    #[test]
+    fn rejects_invalid_http_dates() {
+        assert_eq!(parse_http_date("Sun, 06 Nov 1994 08:49:37"), None);
+        assert_eq!(parse_http_date("Sun, 06 Nov 1994 24:00:00 GMT"), None);
+        assert_eq!(parse_http_date("Sun, 32 Nov 1994 08:49:37 GMT"), None);
+        assert_eq!(parse_http_date("Sun, 06 November 1994 08:49:37 GMT"), None);
+        assert_eq!(parse_http_date("Sunday, 06-Nov-94 08:49:37 GMT"), None);
+        assert_eq!(parse_http_date(""), None);
+    }
+
+    #[test]
+    fn matches_etags_weakly_for_if_none_match() {
+        assert!(fresh("\"abc\"", "\"abc\""));
+        assert!(fresh("W/\"abc\"", "\"abc\""));
+        assert!(fresh("\"abc\"", "W/\"abc\""));
+        assert!(fresh("\"old\", W/\"abc\"", "\"abc\""));
+        assert!(fresh("*", "\"abc\""));
+        assert!(!fresh("\"abcd\"", "\"abc\""));
+        assert!(!fresh("\"ABC\"", "\"abc\""));
+    }
+
+    #[test]
+    fn matches_etags_strongly_for_if_range() {
+        let response = Response::bytes("", "text/plain").with_etag("abc");
+        assert!(response.satisfies_if_range("\"abc\""));
+        assert!(!response.satisfies_if_range("W/\"abc\""));
+
+        let response = Response::bytes("", "text/plain").with_etag("W/\"abc\"");
+        assert!(!response.satisfies_if_range("\"abc\""));
+        assert!(!response.satisfies_if_range("W/\"abc\""));
+    }
+
+    #[test]
+    fn answers_not_modified() {
+        let response = send(Request::get("winit:///?etag=abc").with_header("If-None-Match", "W/\"abc\""));
+        assert_eq!(response.status, 304);
+        assert_eq!(response.content_length(), None);
+        assert_eq!(response.etag(), Some("\"abc\""));
+
+        let response = send(Request::get("winit:///?etag=abc").with_header("If-None-Match", "\"new\""));
+        assert_eq!(response.status, 200);
+    }
+
+    #[test]
+    fn compares_modification_dates() {
+        let response = send(Request::get("winit:///").with_header("If-Modified-Since", "Sun, 06 Nov 1994 08:49:37 GMT"));
+        assert_eq!(response.status, 304);
+
+        let response = send(Request::get("winit:///").with_header("If-Modified-Since", "Sun, 06 Nov 1994 08:49:36 GMT"));
+        assert_eq!(response.status, 200);
+
+        // `If-None-Match` takes precedence
+        let response = send(
+            Request::get("winit:///?etag=abc")
+                .with_header("If-None-Match", "\"new\"")
+                .with_header("If-Modified-Since", "Sun, 06 Nov 1994 08:49:37 GMT")
+        );
+        assert_eq!(response.status, 200);
+
+        let response = send(Request::new("POST", "winit:///").with_header("If-Modified-Since", "Sun, 06 Nov 1994 08:49:37 GMT"));
+        assert_eq!(response.status, 200);
+    }
+}
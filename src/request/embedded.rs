@@ -0,0 +1,97 @@
+use std::io;
+
+use super::{
+    static_dir::{resolve_virtual, serve_file},
+    BoxedBody, Request, RequestHandler, Response
+};
+
+/// A directory tree embedded into the binary with `include_assets!`
+#[derive(Debug)]
+pub struct EmbeddedDir {
+    files: &'static [EmbeddedFile]
+}
+
+/// A file embedded into the binary with `include_assets!`
+#[derive(Debug)]
+pub struct EmbeddedFile {
+    /// The path relative to the embedded directory, e.g. `js/app.js`
+    pub path: &'static str,
+    pub contents: &'static [u8],
+    pub mime_type: &'static str,
+    /// A quoted hash of the contents
+    pub etag: &'static str
+}
+
+impl EmbeddedDir {
+    /// Wrap a list of files sorted by path. Use `include_assets!` rather than calling this directly.
+    #[doc(hidden)]
+    pub const fn new(files: &'static [EmbeddedFile]) -> Self {
+        Self { files }
+    }
+
+    /// Get a file by its path relative to the embedded directory
+    pub fn get(&self, path: &str) -> Option<&'static EmbeddedFile> {
+        let path = path.trim_start_matches('/');
+        let files = self.files;
+
+        files.binary_search_by(|file| file.path.cmp(path)).ok().map(|index| &files[index])
+    }
+
+    pub fn files(&self) -> &'static [EmbeddedFile] {
+        self.files
+    }
+}
+
+/// Serves files embedded into the binary with `include_assets!`.
+///
+/// Paths resolve like `StaticDir`: directories are served through their index file,
+/// and missing files get `404 Not Found`. `Range` requests get the requested part of the file.
+///
+/// ```ignore
+/// static ASSETS: EmbeddedDir = winit_webview::include_assets!("dist");
+///
+/// let builder = WebViewBuilder::with_request_handler(Embedded::new(&ASSETS));
+/// ```
+pub struct Embedded {
+    dir: &'static EmbeddedDir,
+    index: String,
+    fallback: Option<String>
+}
+
+impl Embedded {
+    pub fn new(dir: &'static EmbeddedDir) -> Self {
+        Self {
+            dir,
+            index: "index.html".to_owned(),
+            fallback: None
+        }
+    }
+
+    /// Set the file served for directories. Defaults to `index.html`.
+    pub fn with_index(mut self, index: impl Into<String>) -> Self {
+        self.index = index.into();
+        self
+    }
+
+    /// Serve the specified file for missing paths without a file extension
+    pub fn with_spa_fallback(mut self, entry_point: impl Into<String>) -> Self {
+        self.fallback = Some(entry_point.into());
+        self
+    }
+}
+
+impl RequestHandler for Embedded {
+    type Read = BoxedBody;
+
+    fn handle(&mut self, request: Request) -> Option<Response<Self::Read>> {
+        Some(serve_file(&request, || {
+            let dir = self.dir;
+            let exists = |path: &str| dir.get(path).is_some();
+            let path = resolve_virtual(&request.relative_path(), &self.index, self.fallback.as_deref(), exists)?;
+            let file = dir.get(&path).ok_or(404u16)?;
+
+            Ok(Response::ok(io::Cursor::new(file.contents), file.mime_type).with_header("ETag", file.etag))
+        }))
+    }
+}
+
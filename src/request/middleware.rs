@@ -0,0 +1,302 @@
+use std::{
+    sync::Arc,
+    time::{Duration, Instant}
+};
+
+use super::{wait_for_response, BoxedBody, Headers, Request, RequestHandler, Responder, Response};
+
+/// Inspects or modifies the requests and responses of a `RequestHandler`.
+///
+/// Attach middleware with `RequestHandler::with`. Layers added later wrap the earlier ones,
+/// so they see requests first and responses last.
+///
+/// ```no_run
+/// # use winit_webview::request::{BoxedBody, Middleware, Request, RequestHandler, Response, StaticDir};
+/// /// Refuse requests without the app's token
+/// struct RequireToken(String);
+///
+/// impl Middleware for RequireToken {
+///     type State = ();
+///
+///     fn on_request(&mut self, request: &mut Request) -> Result<(), Response<BoxedBody>> {
+///         if request.header("X-Token") == Some(self.0.as_str()) {
+///             Ok(())
+///         } else {
+///             Err(Response::bytes("Forbidden", "text/plain").with_status(403).boxed())
+///         }
+///     }
+/// }
+///
+/// let handler = StaticDir::new("dist").with(RequireToken("secret".to_owned()));
+/// ```
+pub trait Middleware: 'static {
+    /// Data carried from a request to its response, e.g. when handling started
+    type State: Send + 'static;
+
+    /// Inspect or modify a request before it reaches the handler.
+    ///
+    /// Returning `Err` answers the request with that response, without calling the handler.
+    fn on_request(&mut self, request: &mut Request) -> Result<Self::State, Response<BoxedBody>>;
+
+    /// Inspect or modify the handler's response. `None` fails the request as not found.
+    ///
+    /// Handlers may respond from any thread, so this only has the state from `on_request` to go on.
+    fn on_response(_state: Self::State, response: Option<Response<BoxedBody>>) -> Option<Response<BoxedBody>> {
+        response
+    }
+}
+
+/// A request handler wrapped in a middleware layer
+pub struct Layered<H: RequestHandler, M: Middleware> {
+    handler: H,
+    layer: M
+}
+
+impl<H: RequestHandler, M: Middleware> Layered<H, M> {
+    pub fn new(handler: H, layer: M) -> Self {
+        Self { handler, layer }
+    }
+}
+
+impl<H: RequestHandler, M: Middleware> RequestHandler for Layered<H, M> {
+    type Read = BoxedBody;
+
+    fn handle(&mut self, request: Request) -> Option<Response<Self::Read>> {
+        wait_for_response(self, request)
+    }
+
+    fn handle_async(&mut self, mut request: Request, responder: Responder<Self::Read>) {
+        let state = match self.layer.on_request(&mut request) {
+            Ok(state) => state,
+            Err(response) => return responder.respond(Some(response))
+        };
+
+        let responder = responder
+            .map_option(move |response| M::on_response(state, response))
+            .map_body(|body: H::Read| Box::new(body) as BoxedBody);
+
+        self.handler.handle_async(request, responder);
+    }
+}
+
+/// Logs each request with its status and how long it took, e.g.
+/// `GET winit:///index.html 200 1.2ms`
+#[derive(Clone)]
+pub struct Logger {
+    sink: Arc<dyn Fn(&str) + Send + Sync>
+}
+
+impl Logger {
+    /// Log to standard error
+    pub fn new() -> Self {
+        Self::with_sink(|line| eprintln!("{}", line))
+    }
+
+    /// Log through a custom function, e.g. a logging framework
+    pub fn with_sink(sink: impl Fn(&str) + Send + Sync + 'static) -> Self {
+        Self { sink: Arc::new(sink) }
+    }
+}
+
+impl Default for Logger {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Middleware for Logger {
+    type State = (Logger, String, Instant);
+
+    fn on_request(&mut self, request: &mut Request) -> Result<Self::State, Response<BoxedBody>> {
+        let line = format!("{} {}", request.method, request.url);
+        Ok((self.clone(), line, Instant::now()))
+    }
+
+    fn on_response(state: Self::State, response: Option<Response<BoxedBody>>) -> Option<Response<BoxedBody>> {
+        let (logger, line, start) = state;
+        let status = match &response {
+            Some(response) => response.status.to_string(),
+            None => "not found".to_owned()
+        };
+
+        (logger.sink)(&format!("{} {} {}", line, status, format_duration(start.elapsed())));
+        response
+    }
+}
+
+/// Adds security headers to responses that don't set them already.
+///
+/// By default, this disables MIME sniffing, referrers and framing by other origins.
+#[derive(Debug, Clone)]
+pub struct SecurityHeaders {
+    headers: Arc<Headers>
+}
+
+impl SecurityHeaders {
+    pub fn new() -> Self {
+        let headers = [
+            ("X-Content-Type-Options", "nosniff"),
+            ("Referrer-Policy", "no-referrer"),
+            ("X-Frame-Options", "SAMEORIGIN")
+        ];
+
+        Self {
+            headers: Arc::new(headers.iter().map(|(name, value)| (name.to_string(), value.to_string())).collect())
+        }
+    }
+
+    /// Add or replace a default header
+    pub fn with_header(mut self, name: impl Into<String>, value: impl Into<String>) -> Self {
+        Arc::make_mut(&mut self.headers).insert(name, value);
+        self
+    }
+
+    /// Set a default `Content-Security-Policy`
+    pub fn with_content_security_policy(self, policy: impl Into<String>) -> Self {
+        self.with_header("Content-Security-Policy", policy)
+    }
+}
+
+impl Default for SecurityHeaders {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Middleware for SecurityHeaders {
+    type State = Arc<Headers>;
+
+    fn on_request(&mut self, _request: &mut Request) -> Result<Self::State, Response<BoxedBody>> {
+        Ok(self.headers.clone())
+    }
+
+    fn on_response(headers: Self::State, response: Option<Response<BoxedBody>>) -> Option<Response<BoxedBody>> {
+        let mut response = response?;
+        for (name, value) in headers.iter() {
+            if !response.headers.contains(name) {
+                response.headers.append(name, value);
+            }
+        }
+
+        Some(response)
+    }
+}
+
+/// Reports how long the handler took to respond in a `Server-Timing` header,
+/// which shows up in the developer tools
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Timing;
+
+impl Middleware for Timing {
+    type State = Instant;
+
+    fn on_request(&mut self, _request: &mut Request) -> Result<Self::State, Response<BoxedBody>> {
+        Ok(Instant::now())
+    }
+
+    fn on_response(start: Self::State, response: Option<Response<BoxedBody>>) -> Option<Response<BoxedBody>> {
+        let elapsed = start.elapsed().as_secs_f64() * 1000.0;
+        Some(response?.with_header("Server-Timing", format!("handler;dur={:.1}", elapsed)))
+    }
+}
+
+fn format_duration(duration: Duration) -> String {
+    if duration < Duration::from_secs(1) {
+        format!("{:.1}ms", duration.as_secs_f64() * 1000.0)
+    } else {
+        format!("{:.2}s", duration.as_secs_f64())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::{self, Read};
+
+    use super::*;
+
+    /// Records the order layers see requests and responses in
+    struct Tag(&'static str);
+
+    impl Middleware for Tag {
+        type State = &'static str;
+
+        fn on_request(&mut self, request: &mut Request) -> Result<Self::State, Response<BoxedBody>> {
+            request.headers.append("X-Order", self.0);
+            Ok(self.0)
+        }
+
+        fn on_response(tag: Self::State, response: Option<Response<BoxedBody>>) -> Option<Response<BoxedBody>> {
+            Some(response?.with_header("X-Order", tag))
+        }
+    }
+
+    /// Refuses every request
+    struct Refuse;
+
+    impl Middleware for Refuse {
+        type State = ();
+
+        fn on_request(&mut self, _request: &mut Request) -> Result<(), Response<BoxedBody>> {
+            Err(Response::bytes("Forbidden", "text/plain").with_status(403).boxed())
+        }
+    }
+
+    fn echo_order(request: Request) -> Option<Response<io::Cursor<Vec<u8>>>> {
+        let order: Vec<&str> = request.headers.get_all("X-Order").collect();
+        Some(Response::bytes(order.join(" "), "text/plain"))
+    }
+
+    fn body(mut response: Response<BoxedBody>) -> String {
+        let mut body = String::new();
+        response.body.read_to_string(&mut body).unwrap();
+        body
+    }
+
+    #[test]
+    fn later_layers_see_requests_first_and_responses_last() {
+        let mut handler = echo_order.with(Tag("inner")).with(Tag("outer"));
+        let response = handler.handle(Request::get("winit:///")).unwrap();
+
+        let order: Vec<&str> = response.headers.get_all("X-Order").collect();
+        assert_eq!(order, ["inner", "outer"]);
+        assert_eq!(body(response), "outer inner");
+    }
+
+    #[test]
+    fn refused_requests_skip_the_handler_and_inner_layers() {
+        let mut handler = (|_request: Request| -> Option<Response<io::Empty>> { panic!("the handler ran") })
+            .with(Tag("inner"))
+            .with(Refuse);
+        let response = handler.handle(Request::get("winit:///")).unwrap();
+
+        assert_eq!(response.status, 403);
+        assert!(!response.headers.contains("X-Order"));
+        assert_eq!(body(response), "Forbidden");
+    }
+
+    #[test]
+    fn security_headers_keep_the_handlers_headers() {
+        let handler = |_request: Request| {
+            Some(Response::bytes("", "text/plain").with_header("X-Frame-Options", "DENY"))
+        };
+        let mut handler = handler.with(SecurityHeaders::new().with_content_security_policy("default-src 'self'"));
+        let response = handler.handle(Request::get("winit:///")).unwrap();
+
+        assert_eq!(response.headers.get_all("X-Frame-Options").collect::<Vec<_>>(), ["DENY"]);
+        assert_eq!(response.headers.get("X-Content-Type-Options"), Some("nosniff"));
+        assert_eq!(response.headers.get("Content-Security-Policy"), Some("default-src 'self'"));
+    }
+
+    #[test]
+    fn timing_reports_the_handlers_duration() {
+        let mut handler = echo_order.with(Timing);
+        let response = handler.handle(Request::get("winit:///")).unwrap();
+
+        let timing = response.headers.get("Server-Timing").unwrap();
+        let duration: f64 = timing.strip_prefix("handler;dur=").unwrap().parse().unwrap();
+        assert!(duration >= 0.0);
+
+        let mut missing = (|_request: Request| -> Option<Response<io::Empty>> { None }).with(Timing);
+        assert!(missing.handle(Request::get("winit:///")).is_none());
+    }
+}
@@ -0,0 +1,460 @@
+use std::{borrow::Cow, collections::HashMap, io, sync::mpsc};
+
+mod cancellation;
+pub use cancellation::CancellationToken;
+
+mod headers;
+pub use headers::Headers;
+
+mod responder;
+pub use responder::Responder;
+
+mod conditional;
+mod range;
+
+mod middleware;
+pub use middleware::{Layered, Logger, Middleware, SecurityHeaders, Timing};
+
+mod proxy;
+pub use proxy::Proxy;
+
+mod router;
+pub use router::Router;
+
+mod static_dir;
+pub use static_dir::StaticDir;
+
+mod embedded;
+pub use embedded::{Embedded, EmbeddedDir, EmbeddedFile};
+
+#[cfg(feature = "archive")]
+mod archive;
+#[cfg(feature = "archive")]
+pub use archive::Archive;
+
+/// A type-erased response body, so one handler can return different kinds of body
+pub type BoxedBody = Box<dyn io::Read + Send>;
+
+/// A type-erased request handler
+pub(crate) type BoxedHandler = Box<dyn FnMut(Request, Responder<BoxedBody>)>;
+
+/// A request made by the page to a custom scheme
+#[derive(Debug, Clone)]
+pub struct Request {
+    /// The HTTP method, e.g. `GET` or `POST`
+    pub method: String,
+    /// The full URL, including the scheme and query string
+    pub url: String,
+    pub headers: Headers,
+    /// The request body. On Linux, this is always empty unless the `v2_40` feature is enabled.
+    pub body: Vec<u8>,
+    /// Cancelled when the page stops waiting for the response.
+    ///
+    /// WebKitGTK doesn't say when a request is abandoned, so on Linux this is only cancelled
+    /// once streaming the body fails, after the handler has responded.
+    pub cancellation: CancellationToken,
+    /// Path parameters captured by a `Router`
+    pub params: Vec<(String, String)>,
+    /// The still-encoded path below the `Router` mount point this request was routed through
+    pub(crate) route_path: Option<String>
+}
+
+impl Request {
+    pub fn new(method: impl Into<String>, url: impl Into<String>) -> Self {
+        Self {
+            method: method.into(),
+            url: url.into(),
+            headers: Headers::new(),
+            body: Vec::new(),
+            cancellation: CancellationToken::new(),
+            params: Vec::new(),
+            route_path: None
+        }
+    }
+
+    /// Create a `GET` request for the specified URL
+    pub fn get(url: impl Into<String>) -> Self {
+        Self::new("GET", url)
+    }
+
+    pub fn with_header(mut self, name: impl Into<String>, value: impl Into<String>) -> Self {
+        self.headers.append(name, value);
+        self
+    }
+
+    pub fn with_body(mut self, body: impl Into<Vec<u8>>) -> Self {
+        self.body = body.into();
+        self
+    }
+
+    /// Get the URL scheme, e.g. `winit`
+    pub fn scheme(&self) -> &str {
+        match self.url.find(':') {
+            Some(index) => &self.url[..index],
+            None => ""
+        }
+    }
+
+    /// Get the path component of the URL, still percent-encoded
+    pub fn raw_path(&self) -> &str {
+        let rest = match self.url.find("://") {
+            Some(index) => &self.url[index + 3..],
+            None => self.url.as_str()
+        };
+
+        let rest = match rest.find(['?', '#']) {
+            Some(index) => &rest[..index],
+            None => rest
+        };
+
+        match rest.find('/') {
+            Some(index) => &rest[index..],
+            None => "/"
+        }
+    }
+
+    /// Get the decoded path component of the URL, e.g. `/index.html`
+    pub fn path(&self) -> Cow<'_, str> {
+        percent_decode(self.raw_path(), false)
+    }
+
+    /// Get the decoded path below the point where a `Router` mounted the handler.
+    ///
+    /// Outside of a router, this is the same as `path`.
+    pub fn relative_path(&self) -> Cow<'_, str> {
+        match &self.route_path {
+            Some(path) => percent_decode(path, false),
+            None => self.path()
+        }
+    }
+
+    /// Get the raw query string, without the leading `?`
+    pub fn query_string(&self) -> Option<&str> {
+        let start = self.url.find('?')? + 1;
+        let query = &self.url[start..];
+
+        match query.find('#') {
+            Some(index) => Some(&query[..index]),
+            None => Some(query)
+        }
+    }
+
+    /// Get the decoded query parameters, in order
+    pub fn query(&self) -> Vec<(String, String)> {
+        self.query_string().map(parse_urlencoded).unwrap_or_default()
+    }
+
+    /// Get the first value of the named query parameter
+    pub fn query_param(&self, name: &str) -> Option<String> {
+        self.query().into_iter().find(|(key, _)| key == name).map(|(_, value)| value)
+    }
+
+    /// Get a path parameter captured by a `Router`
+    pub fn param(&self, name: &str) -> Option<&str> {
+        self.params.iter().find(|(key, _)| key == name).map(|(_, value)| value.as_str())
+    }
+
+    /// Check whether the page has stopped waiting for the response
+    pub fn is_cancelled(&self) -> bool {
+        self.cancellation.is_cancelled()
+    }
+
+    /// Get the first value of the named header
+    pub fn header(&self, name: &str) -> Option<&str> {
+        self.headers.get(name)
+    }
+
+    /// Get the body as UTF-8 text
+    pub fn text(&self) -> Option<&str> {
+        std::str::from_utf8(&self.body).ok()
+    }
+
+    /// Get the decoded fields of an `application/x-www-form-urlencoded` body
+    pub fn form(&self) -> Vec<(String, String)> {
+        self.text().map(parse_urlencoded).unwrap_or_default()
+    }
+}
+
+/// A response to a custom scheme request
+pub struct Response<T: io::Read> {
+    /// The HTTP status code
+    pub status: u16,
+    /// Additional response headers. `Content-Type` is taken from `mime_type` unless set here.
+    pub headers: Headers,
+    pub body: T,
+    pub mime_type: String
+}
+
+impl<T: io::Read> Response<T> {
+    /// Create a `200 OK` response
+    pub fn ok(body: T, mime_type: impl Into<String>) -> Self {
+        Self {
+            status: 200,
+            headers: Headers::new(),
+            body,
+            mime_type: mime_type.into()
+        }
+    }
+
+    pub fn with_status(mut self, status: u16) -> Self {
+        self.status = status;
+        self
+    }
+
+    /// Add a header, keeping any existing values
+    pub fn with_header(mut self, name: impl Into<String>, value: impl Into<String>) -> Self {
+        self.headers.append(name, value);
+        self
+    }
+
+    /// Set the `Content-Length` header. Without it, the body is streamed until it ends.
+    pub fn with_content_length(mut self, len: u64) -> Self {
+        self.headers.insert("Content-Length", len.to_string());
+        self
+    }
+
+    /// Get the length of the body, if known
+    pub fn content_length(&self) -> Option<u64> {
+        self.headers.get("Content-Length")?.trim().parse().ok()
+    }
+
+    /// Convert the body, keeping the status and headers
+    pub fn map_body<U: io::Read>(self, f: impl FnOnce(T) -> U) -> Response<U> {
+        Response {
+            status: self.status,
+            headers: self.headers,
+            body: f(self.body),
+            mime_type: self.mime_type
+        }
+    }
+
+    /// Box the body, so responses with different body types can be returned from one handler
+    pub fn boxed(self) -> Response<BoxedBody>
+    where
+        T: Send + 'static
+    {
+        self.map_body(|body| Box::new(body) as BoxedBody)
+    }
+
+    /// Get the headers as sent to the webview, with repeated headers joined and `Content-Type` filled in.
+    ///
+    /// `Set-Cookie` can't be joined like other headers, so each of its values stays a field of its own.
+    pub fn header_fields(&self) -> Vec<(String, String)> {
+        let mut fields: Vec<(String, String)> = Vec::new();
+        for (name, value) in self.headers.iter() {
+            let existing = fields
+                .iter_mut()
+                .find(|(key, _)| key.eq_ignore_ascii_case(name))
+                .filter(|_| !name.eq_ignore_ascii_case("Set-Cookie"));
+
+            match existing {
+                Some((_, existing)) => {
+                    existing.push_str(", ");
+                    existing.push_str(value);
+                }
+                None => fields.push((name.to_owned(), value.to_owned()))
+            }
+        }
+
+        if !self.headers.contains("Content-Type") {
+            fields.push(("Content-Type".to_owned(), self.mime_type.clone()));
+        }
+
+        fields
+    }
+}
+
+impl Response<io::Cursor<Vec<u8>>> {
+    /// Create a `200 OK` response from an in-memory body
+    pub fn bytes(body: impl Into<Vec<u8>>, mime_type: impl Into<String>) -> Self {
+        let body = body.into();
+        let len = body.len() as u64;
+
+        Self::ok(io::Cursor::new(body), mime_type).with_content_length(len)
+    }
+}
+
+impl<T: io::Read + Default> Response<T> {
+    /// Create a response with an empty body
+    pub fn empty(status: u16) -> Self {
+        Self::ok(T::default(), "text/plain").with_status(status)
+    }
+
+    /// Create a `404 Not Found` response
+    pub fn not_found() -> Self {
+        Self::empty(404)
+    }
+
+    /// Create a `302 Found` response redirecting to the specified URL
+    pub fn redirect(location: impl Into<String>) -> Self {
+        Self::empty(302).with_header("Location", location)
+    }
+}
+
+pub trait RequestHandler: 'static {
+    /// The response body. Bodies are streamed to the webview from a background thread.
+    type Read: io::Read + Send + 'static;
+
+    /// Handle a request to the specified path
+    fn handle_request(&mut self, _uri: &str) -> Option<Response<Self::Read>> {
+        None
+    }
+
+    /// Handle a request with its method, headers and body.
+    ///
+    /// Handlers built from others, like `Router` and `Layered`, answer this by waiting for `handle_async`.
+    fn handle(&mut self, request: Request) -> Option<Response<Self::Read>> {
+        self.handle_request(&request.relative_path())
+    }
+
+    /// Handle a request without blocking the UI thread.
+    ///
+    /// Override this to hand slow work (disk, databases, subprocesses) off to another thread,
+    /// and complete the request from there through the responder. Long-running work can
+    /// stop early once the responder is cancelled, except on Linux, where requests are
+    /// only cancelled after a response has been given.
+    fn handle_async(&mut self, request: Request, responder: Responder<Self::Read>) {
+        responder.respond(self.handle(request))
+    }
+
+    /// Wrap the handler in a middleware layer, e.g. `StaticDir::new("dist").with(Logger::new())`
+    fn with<M: Middleware>(self, layer: M) -> Layered<Self, M>
+    where
+        Self: Sized
+    {
+        Layered::new(self, layer)
+    }
+}
+
+impl<F, R> RequestHandler for F
+where
+    F: FnMut(Request) -> Option<Response<R>> + 'static,
+    R: io::Read + Send + 'static
+{
+    type Read = R;
+
+    fn handle(&mut self, request: Request) -> Option<Response<Self::Read>> {
+        (self)(request)
+    }
+}
+
+/// Handle a request through `handle_async`, blocking until the handler responds
+pub(crate) fn wait_for_response<H: RequestHandler>(handler: &mut H, request: Request) -> Option<Response<H::Read>> {
+    let (sender, receiver) = mpsc::channel();
+    let responder = Responder::new(request.cancellation.clone(), move |response| {
+        sender.send(response).ok();
+    });
+
+    handler.handle_async(request, responder);
+    receiver.recv().ok().flatten()
+}
+
+pub(crate) fn boxed<T: RequestHandler>(mut handler: T) -> BoxedHandler {
+    Box::new(move |request, responder: Responder<BoxedBody>| {
+        handler.handle_async(request, responder.map_body(|body: T::Read| Box::new(body) as BoxedBody))
+    })
+}
+
+/// Combine the handler for the `winit` scheme with the handlers for any other schemes.
+///
+/// A handler registered for `winit` with `WebViewBuilder::with_scheme` takes precedence.
+pub(crate) fn scheme_handlers(
+    default: impl RequestHandler,
+    mut schemes: HashMap<String, BoxedHandler>
+) -> HashMap<String, BoxedHandler> {
+    schemes.entry("winit".to_owned()).or_insert_with(|| boxed(default));
+
+    // Revalidation is handled here, so every handler gets it by just setting validators
+    schemes
+        .into_iter()
+        .map(|(scheme, handler)| (scheme, conditional::conditional(handler)))
+        .collect()
+}
+
+/// A request handler that always 404s
+pub struct NullRequestHandler;
+
+impl RequestHandler for NullRequestHandler {
+    type Read = io::Empty;
+
+    fn handle_request(&mut self, _uri: &str) -> Option<Response<Self::Read>> {
+        None
+    }
+}
+
+/// Get the scheme and host of a URL, e.g. `winit://app` for `winit://app/index.html`
+pub(crate) fn origin(url: &str) -> &str {
+    let start = url.find("://").map(|index| index + 3).unwrap_or(0);
+    match url[start..].find(['/', '?', '#']) {
+        Some(index) => &url[..start + index],
+        None => url
+    }
+}
+
+/// Decode `%XX` escapes, and `+` as a space if requested
+fn percent_decode(input: &str, plus_as_space: bool) -> Cow<'_, str> {
+    if !(input.contains('%') || plus_as_space && input.contains('+')) {
+        return Cow::Borrowed(input);
+    }
+
+    let bytes = input.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut index = 0;
+    while index < bytes.len() {
+        match bytes[index] {
+            b'%' if index + 2 < bytes.len()
+                && bytes[index + 1].is_ascii_hexdigit()
+                && bytes[index + 2].is_ascii_hexdigit() =>
+            {
+                let hex = std::str::from_utf8(&bytes[index + 1..index + 3]).unwrap();
+                decoded.push(u8::from_str_radix(hex, 16).unwrap());
+                index += 3;
+                continue;
+            }
+            b'+' if plus_as_space => decoded.push(b' '),
+            byte => decoded.push(byte)
+        }
+
+        index += 1;
+    }
+
+    Cow::Owned(String::from_utf8_lossy(&decoded).into_owned())
+}
+
+/// Parse `key=value&key=value` pairs
+fn parse_urlencoded(input: &str) -> Vec<(String, String)> {
+    input
+        .split('&')
+        .filter(|pair| !pair.is_empty())
+        .map(|pair| {
+            let (key, value) = match pair.find('=') {
+                Some(index) => (&pair[..index], &pair[index + 1..]),
+                None => (pair, "")
+            };
+
+            (percent_decode(key, true).into_owned(), percent_decode(value, true).into_owned())
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn joins_repeated_headers_except_cookies() {
+        let response = Response::bytes("", "text/plain")
+            .with_header("Cache-Control", "no-cache")
+            .with_header("cache-control", "no-store")
+            .with_header("Set-Cookie", "a=1; Path=/")
+            .with_header("Set-Cookie", "b=2; Expires=Wed, 21 Oct 2026 07:28:00 GMT");
+
+        assert_eq!(response.header_fields(), [
+            ("Content-Length".to_owned(), "0".to_owned()),
+            ("Cache-Control".to_owned(), "no-cache, no-store".to_owned()),
+            ("Set-Cookie".to_owned(), "a=1; Path=/".to_owned()),
+            ("Set-Cookie".to_owned(), "b=2; Expires=Wed, 21 Oct 2026 07:28:00 GMT".to_owned()),
+            ("Content-Type".to_owned(), "text/plain".to_owned())
+        ]);
+    }
+}
@@ -0,0 +1,477 @@
+use std::{
+    io::{self, BufRead, BufReader, Read, Write},
+    net::{Shutdown, TcpStream, ToSocketAddrs},
+    thread,
+    time::Duration
+};
+
+use super::{origin, BoxedBody, CancellationToken, Headers, Request, RequestHandler, Responder, Response};
+
+/// Headers that only apply to a single connection, so aren't forwarded, along with any listed in `Connection`
+const HOP_BY_HOP: [&str; 8] = [
+    "Connection",
+    "Keep-Alive",
+    "Proxy-Authenticate",
+    "Proxy-Authorization",
+    "TE",
+    "Trailer",
+    "Transfer-Encoding",
+    "Upgrade"
+];
+
+/// Forwards requests to a local HTTP server, e.g. a frontend development server.
+///
+/// The page stays on the custom scheme, so IPC and content security policies behave like
+/// they do when serving built assets. The method, path, query, headers and body are
+/// forwarded, and the server's response is streamed back. Requests that can't reach the
+/// server get `502 Bad Gateway`, or `504 Gateway Timeout` when it stops responding.
+///
+/// Only plain HTTP is supported, so WebSocket connections such as the one for hot reloading
+/// can't go through the proxy. Point them at the server itself, e.g. for Vite:
+///
+/// ```js
+/// // vite.config.js
+/// export default {
+///     server: { hmr: { protocol: "ws", host: "localhost", clientPort: 5173 } }
+/// };
+/// ```
+///
+/// Requests cancelled by the page close their connection to the server.
+///
+/// ```no_run
+/// # use winit_webview::{request::Proxy, WebViewBuilder};
+/// let builder = WebViewBuilder::with_request_handler(Proxy::new("localhost:5173"));
+/// ```
+#[derive(Debug, Clone)]
+pub struct Proxy {
+    authority: String,
+    timeout: Duration
+}
+
+impl Proxy {
+    /// Forward requests to the specified address, e.g. `localhost:5173` or `http://127.0.0.1:8080`
+    pub fn new(address: impl AsRef<str>) -> Self {
+        let address = address.as_ref();
+        let address = address.strip_prefix("http://").unwrap_or(address);
+
+        Self {
+            authority: address.trim_end_matches('/').to_owned(),
+            timeout: Duration::from_secs(30)
+        }
+    }
+
+    /// Set how long to wait for the server to connect or send data. Defaults to 30 seconds.
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+}
+
+impl RequestHandler for Proxy {
+    type Read = BoxedBody;
+
+    fn handle_async(&mut self, request: Request, responder: Responder<Self::Read>) {
+        let proxy = self.clone();
+
+        thread::spawn(move || {
+            if request.is_cancelled() {
+                return responder.respond(None);
+            }
+
+            let response = proxy.forward(&request).unwrap_or_else(|err| {
+                let status = match err.kind() {
+                    io::ErrorKind::TimedOut | io::ErrorKind::WouldBlock => 504,
+                    _ => 502
+                };
+
+                let message = format!("Failed to proxy {} to {}: {}", request.url, proxy.authority, err);
+                Response::bytes(message, "text/plain").with_status(status).boxed()
+            });
+
+            responder.respond(Some(response));
+        });
+    }
+}
+
+impl Proxy {
+    fn connect(&self) -> io::Result<TcpStream> {
+        let mut last_err = io::Error::new(io::ErrorKind::NotFound, "Address didn't resolve");
+        for addr in self.authority.to_socket_addrs()? {
+            match TcpStream::connect_timeout(&addr, self.timeout) {
+                Ok(stream) => return Ok(stream),
+                Err(err) => last_err = err
+            }
+        }
+
+        Err(last_err)
+    }
+
+    /// Send the request to the server and read the head of its response
+    fn forward(&self, request: &Request) -> io::Result<Response<BoxedBody>> {
+        let mut stream = self.connect()?;
+        stream.set_read_timeout(Some(self.timeout))?;
+        stream.set_write_timeout(Some(self.timeout))?;
+
+        // Stop waiting for the server once the page stops waiting for the response
+        let connection = stream.try_clone()?;
+        request.cancellation.on_cancel(move || {
+            connection.shutdown(Shutdown::Both).ok();
+        });
+
+        let target = match request.query_string() {
+            Some(query) => format!("{}?{}", request.raw_path(), query),
+            None => request.raw_path().to_owned()
+        };
+
+        let mut head = format!("{} {} HTTP/1.1\r\nHost: {}\r\nConnection: close\r\n", request.method, target, self.authority);
+        for (name, value) in request.headers.iter() {
+            // The body is relayed as is, so ask for it uncompressed
+            let replaced = ["Host", "Content-Length", "Accept-Encoding"].iter().any(|skip| skip.eq_ignore_ascii_case(name));
+            let malformed = name.contains(['\r', '\n']) || value.contains(['\r', '\n']);
+            if !(replaced || malformed || is_hop_by_hop(name, &request.headers)) {
+                head.push_str(&format!("{}: {}\r\n", name, value));
+            }
+        }
+
+        if !request.body.is_empty() || !matches!(request.method.as_str(), "GET" | "HEAD") {
+            head.push_str(&format!("Content-Length: {}\r\n", request.body.len()));
+        }
+
+        head.push_str("\r\n");
+        stream.write_all(head.as_bytes())?;
+        stream.write_all(&request.body)?;
+
+        let mut reader = BufReader::new(stream);
+        let (status, mut headers) = loop {
+            let (status, headers) = read_head(&mut reader)?;
+
+            // Skip interim responses such as `100 Continue`
+            if !(100..200).contains(&status) {
+                break (status, headers);
+            }
+        };
+
+        let chunked = headers.get("Transfer-Encoding").is_some_and(|value| value.to_ascii_lowercase().contains("chunked"));
+        let body: BoxedBody = if request.method == "HEAD" || status == 204 || status == 304 {
+            // There's no body, whatever length the server describes
+            headers.remove("Content-Length");
+            Box::new(io::empty())
+        } else if chunked {
+            headers.remove("Content-Length");
+            Box::new(Chunked { inner: reader, remaining: 0, done: false })
+        } else {
+            match headers.get("Content-Length").and_then(|len| len.trim().parse().ok()) {
+                Some(len) => Box::new(reader.take(len)),
+                None => Box::new(reader)
+            }
+        };
+
+        let mut headers: Headers = headers.iter().filter(|(name, _)| !is_hop_by_hop(name, &headers)).collect();
+        let body = Box::new(Cancellable { inner: body, cancellation: request.cancellation.clone() });
+
+        // Keep redirects within the server on the custom scheme
+        let server_origin = format!("http://{}", self.authority);
+        if let Some(location) = headers.get("Location").and_then(|location| location.strip_prefix(&server_origin)) {
+            let location = format!("{}{}", origin(&request.url), location);
+            headers.insert("Location", location);
+        }
+
+        let mime_type = headers.get("Content-Type").unwrap_or("application/octet-stream").to_owned();
+        Ok(Response { status, headers, body, mime_type })
+    }
+}
+
+fn is_hop_by_hop(name: &str, headers: &Headers) -> bool {
+    let mut listed = headers.get_all("Connection").flat_map(|value| value.split(','));
+    HOP_BY_HOP.iter().any(|header| header.eq_ignore_ascii_case(name))
+        || listed.any(|header| header.trim().eq_ignore_ascii_case(name))
+}
+
+/// Read a status line and headers
+fn read_head(reader: &mut impl BufRead) -> io::Result<(u16, Headers)> {
+    let line = read_line(reader)?;
+    let mut parts = line.split_whitespace();
+    let status = match (parts.next(), parts.next().and_then(|status| status.parse().ok())) {
+        (Some(version), Some(status)) if version.starts_with("HTTP/1.") => status,
+        _ => return Err(io::Error::new(io::ErrorKind::InvalidData, "Malformed status line"))
+    };
+
+    let mut headers = Headers::new();
+    loop {
+        let line = read_line(reader)?;
+        if line.is_empty() {
+            return Ok((status, headers));
+        }
+
+        if let Some((name, value)) = line.split_once(':') {
+            headers.append(name.trim(), value.trim());
+        }
+    }
+}
+
+/// Read a line, without its line ending
+fn read_line(reader: &mut impl BufRead) -> io::Result<String> {
+    let mut line = String::new();
+    if reader.take(64 * 1024).read_line(&mut line)? == 0 || !line.ends_with('\n') {
+        return Err(io::ErrorKind::UnexpectedEof.into());
+    }
+
+    Ok(line.trim_end_matches(['\r', '\n']).to_owned())
+}
+
+/// Fails reads once the request is cancelled, rather than passing on a truncated body
+struct Cancellable<R> {
+    inner: R,
+    cancellation: CancellationToken
+}
+
+impl<R: Read> Read for Cancellable<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if self.cancellation.is_cancelled() {
+            return Err(io::Error::new(io::ErrorKind::Other, "The request was cancelled"));
+        }
+
+        self.inner.read(buf)
+    }
+}
+
+/// Decodes a body sent with `Transfer-Encoding: chunked`
+struct Chunked<R> {
+    inner: R,
+    /// The bytes left in the current chunk
+    remaining: u64,
+    done: bool
+}
+
+impl<R: BufRead> Read for Chunked<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if self.done || buf.is_empty() {
+            return Ok(0);
+        }
+
+        if self.remaining == 0 {
+            let line = read_line(&mut self.inner)?;
+            let size = line.split(';').next().unwrap_or_default().trim();
+            self.remaining = u64::from_str_radix(size, 16)
+                .map_err(|_| io::Error::new(io::ErrorKind::InvalidData, "Malformed chunk size"))?;
+
+            if self.remaining == 0 {
+                // Skip any trailers
+                while !read_line(&mut self.inner)?.is_empty() {}
+                self.done = true;
+                return Ok(0);
+            }
+        }
+
+        let max = self.remaining.min(buf.len() as u64) as usize;
+        let len = self.inner.read(&mut buf[..max])?;
+        if len == 0 {
+            return Err(io::ErrorKind::UnexpectedEof.into());
+        }
+
+        self.remaining -= len as u64;
+        if self.remaining == 0 {
+            // Each chunk ends with a line break
+            read_line(&mut self.inner)?;
+        }
+
+        Ok(len)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        net::TcpListener,
+        sync::mpsc::{self, Receiver}
+    };
+
+    use super::*;
+    use crate::request::CancellationToken;
+
+    /// Start a stand-in server that answers one request with `response`,
+    /// and passes on the request it received
+    fn serve(response: impl Into<String>) -> (String, Receiver<String>) {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let address = listener.local_addr().unwrap().to_string();
+        let response = response.into().replace("{address}", &address);
+        let (sender, receiver) = mpsc::channel();
+
+        thread::spawn(move || {
+            let (stream, _) = listener.accept().unwrap();
+            let mut reader = BufReader::new(stream.try_clone().unwrap());
+
+            let mut received = String::new();
+            let mut len = 0;
+            loop {
+                let line = read_line(&mut reader).unwrap();
+                if let Some((name, value)) = line.split_once(':') {
+                    if name.eq_ignore_ascii_case("Content-Length") {
+                        len = value.trim().parse().unwrap();
+                    }
+                }
+
+                received.push_str(&line);
+                received.push('\n');
+                if line.is_empty() {
+                    break;
+                }
+            }
+
+            let mut body = vec![0; len];
+            reader.read_exact(&mut body).unwrap();
+            received.push_str(&String::from_utf8(body).unwrap());
+            sender.send(received).ok();
+
+            (&stream).write_all(response.as_bytes()).unwrap();
+        });
+
+        (address, receiver)
+    }
+
+    fn send(proxy: Proxy, request: Request) -> Response<BoxedBody> {
+        let (sender, receiver) = mpsc::channel();
+        let mut proxy = proxy;
+        proxy.handle_async(request, Responder::new(CancellationToken::new(), move |response| {
+            sender.send(response).unwrap();
+        }));
+
+        receiver.recv().unwrap().unwrap()
+    }
+
+    fn body(response: &mut Response<BoxedBody>) -> String {
+        let mut body = String::new();
+        response.body.read_to_string(&mut body).unwrap();
+        body
+    }
+
+    #[test]
+    fn forwards_requests() {
+        let (address, received) = serve("HTTP/1.1 201 Created\r\nContent-Type: application/json\r\nContent-Length: 2\r\n\r\n{}");
+        let request = Request::new("POST", "winit://app/api/items?sort=name&page=2")
+            .with_header("X-Token", "abc")
+            .with_header("Accept-Encoding", "gzip")
+            .with_header("Connection", "keep-alive, X-Hop")
+            .with_header("X-Hop", "1")
+            .with_header("Upgrade", "websocket")
+            .with_body("name=value");
+
+        let mut response = send(Proxy::new(format!("http://{}/", address)), request);
+        assert_eq!(response.status, 201);
+        assert_eq!(response.mime_type, "application/json");
+        assert_eq!(body(&mut response), "{}");
+
+        let received = received.recv().unwrap();
+        let mut lines = received.lines();
+        assert_eq!(lines.next(), Some("POST /api/items?sort=name&page=2 HTTP/1.1"));
+        assert!(received.contains(&format!("Host: {}\n", address)));
+        assert!(received.contains("X-Token: abc\n"));
+        assert!(received.contains("Content-Length: 10\n"));
+        assert!(received.contains("Connection: close\n"));
+        assert!(!received.contains("keep-alive"));
+        assert!(!received.contains("Upgrade"));
+        assert!(!received.contains("X-Hop"));
+        assert!(!received.contains("gzip"));
+        assert!(received.ends_with("\n\nname=value"));
+    }
+
+    #[test]
+    fn strips_hop_by_hop_response_headers() {
+        let (address, _) = serve(
+            "HTTP/1.1 200 OK\r\nConnection: close, X-Hop\r\nKeep-Alive: timeout=5\r\nX-Hop: 1\r\nX-Kept: 1\r\nX-Kept: 2\r\nContent-Length: 0\r\n\r\n"
+        );
+
+        let response = send(Proxy::new(address), Request::get("winit:///"));
+        assert!(response.headers.get("Connection").is_none());
+        assert!(response.headers.get("Keep-Alive").is_none());
+        assert!(response.headers.get("X-Hop").is_none());
+        assert_eq!(response.headers.get_all("X-Kept").collect::<Vec<_>>(), ["1", "2"]);
+    }
+
+    #[test]
+    fn decodes_chunked_bodies() {
+        let (address, _) = serve(concat!(
+            "HTTP/1.1 100 Continue\r\n\r\n",
+            "HTTP/1.1 200 OK\r\nTransfer-Encoding: chunked\r\nContent-Length: 99\r\n\r\n",
+            "5\r\nhello\r\n7;name=value\r\n, world\r\n0\r\nX-Trailer: 1\r\n\r\n"
+        ));
+
+        let mut response = send(Proxy::new(address), Request::get("winit:///"));
+        assert_eq!(response.status, 200);
+        assert!(response.headers.get("Transfer-Encoding").is_none());
+        assert!(response.content_length().is_none());
+        assert_eq!(body(&mut response), "hello, world");
+    }
+
+    #[test]
+    fn rewrites_redirects_to_the_server() {
+        let (address, _) = serve("HTTP/1.1 302 Found\r\nLocation: http://{address}/login?next=%2F\r\nContent-Length: 0\r\n\r\n");
+        let response = send(Proxy::new(address), Request::get("winit://app/account"));
+        assert_eq!(response.headers.get("Location"), Some("winit://app/login?next=%2F"));
+
+        let (address, _) = serve("HTTP/1.1 302 Found\r\nLocation: https://example.com/\r\nContent-Length: 0\r\n\r\n");
+        let response = send(Proxy::new(address), Request::get("winit://app/account"));
+        assert_eq!(response.headers.get("Location"), Some("https://example.com/"));
+    }
+
+    #[test]
+    fn drops_the_length_of_empty_bodies() {
+        let (address, _) = serve("HTTP/1.1 200 OK\r\nContent-Length: 1234\r\n\r\n");
+        let mut response = send(Proxy::new(address), Request::new("HEAD", "winit:///"));
+        assert_eq!(response.content_length(), None);
+        assert_eq!(body(&mut response), "");
+
+        let (address, _) = serve("HTTP/1.1 304 Not Modified\r\nContent-Length: 1234\r\n\r\n");
+        let response = send(Proxy::new(address), Request::get("winit:///"));
+        assert_eq!(response.status, 304);
+        assert_eq!(response.content_length(), None);
+    }
+
+    #[test]
+    fn closes_the_connection_when_cancelled() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let address = listener.local_addr().unwrap().to_string();
+        let (sender, closed) = mpsc::channel();
+
+        thread::spawn(move || {
+            let (mut stream, _) = listener.accept().unwrap();
+            let mut reader = BufReader::new(stream.try_clone().unwrap());
+            while !read_line(&mut reader).unwrap().is_empty() {}
+
+            // Send part of the body, then wait for the proxy to hang up
+            stream.write_all(b"HTTP/1.1 200 OK\r\nContent-Length: 100\r\n\r\nhello").unwrap();
+            sender.send(reader.read_to_end(&mut Vec::new()).is_ok()).ok();
+        });
+
+        let request = Request::get("winit:///");
+        let cancellation = request.cancellation.clone();
+        let mut response = send(Proxy::new(address), request);
+        cancellation.cancel();
+
+        assert!(response.body.read_to_end(&mut Vec::new()).is_err());
+        assert_eq!(closed.recv_timeout(Duration::from_secs(5)), Ok(true));
+    }
+
+    #[test]
+    fn fails_with_bad_gateway() {
+        // Nothing listens on the address once the listener is closed
+        let address = TcpListener::bind("127.0.0.1:0").unwrap().local_addr().unwrap();
+        let response = send(Proxy::new(address.to_string()), Request::get("winit:///"));
+        assert_eq!(response.status, 502);
+
+        let (address, _) = serve("not HTTP\r\n\r\n");
+        let response = send(Proxy::new(address), Request::get("winit:///"));
+        assert_eq!(response.status, 502);
+    }
+
+    #[test]
+    fn fails_with_gateway_timeout() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let proxy = Proxy::new(listener.local_addr().unwrap().to_string()).with_timeout(Duration::from_millis(100));
+
+        // The connection is accepted by the OS, but never answered
+        let response = send(proxy, Request::get("winit:///"));
+        assert_eq!(response.status, 504);
+        drop(listener);
+    }
+}
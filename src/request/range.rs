@@ -0,0 +1,194 @@
+use std::io::{self, SeekFrom};
+
+use super::{Request, Response};
+
+impl<T: io::Read + io::Seek> Response<T> {
+    /// Answer the request's `Range` header with the requested part of the body.
+    ///
+    /// A satisfiable range gets `206 Partial Content` with a `Content-Range`, and an unsatisfiable
+    /// one `416 Range Not Satisfiable`. Requests other than `GET`, without a range, with several ranges,
+    /// with an outdated `If-Range`, or for a response other than `200 OK` get the whole body.
+    /// Set any validators before calling this. The body is read from its current position,
+    /// up to `Content-Length` if it's set.
+    pub fn ranged(mut self, request: &Request) -> io::Result<Response<io::Take<T>>> {
+        let base = self.body.stream_position()?;
+        let len = match self.content_length() {
+            Some(len) => len,
+            None => {
+                let end = self.body.seek(SeekFrom::End(0))?;
+                self.body.seek(SeekFrom::Start(base))?;
+                end.saturating_sub(base)
+            }
+        };
+
+        self.headers.insert("Accept-Ranges", "bytes");
+
+        // A stale `If-Range` means the client's partial copy is outdated, so send everything
+        let if_range = request.header("If-Range").map_or(true, |value| self.satisfies_if_range(value));
+
+        let range = match request.header("Range") {
+            Some(range) if request.method == "GET" && self.status == 200 && if_range => parse_range(range, len),
+            _ => None
+        };
+
+        match range {
+            None => Ok(self.with_content_length(len).map_body(|body| body.take(len))),
+            Some(Ok((start, end))) => {
+                self.body.seek(SeekFrom::Start(base + start))?;
+
+                let count = end - start + 1;
+                let response = self
+                    .with_status(206)
+                    .with_content_length(count)
+                    .with_header("Content-Range", format!("bytes {}-{}/{}", start, end, len));
+
+                Ok(response.map_body(|body| body.take(count)))
+            }
+            Some(Err(())) => {
+                let response = self
+                    .with_status(416)
+                    .with_content_length(0)
+                    .with_header("Content-Range", format!("bytes */{}", len));
+
+                Ok(response.map_body(|body| body.take(0)))
+            }
+        }
+    }
+}
+
+/// Parse a single `bytes=start-end` range into inclusive offsets within a body of `len` bytes.
+///
+/// Returns `None` for headers that should be ignored, and `Some(Err(()))` for ranges
+/// that can't be satisfied.
+fn parse_range(header: &str, len: u64) -> Option<Result<(u64, u64), ()>> {
+    let (unit, spec) = header.split_once('=')?;
+    if !unit.trim().eq_ignore_ascii_case("bytes") || spec.contains(',') {
+        return None;
+    }
+
+    let (start, end) = spec.split_once('-')?;
+    let (start, end) = (start.trim(), end.trim());
+
+    if start.is_empty() {
+        // A suffix range, e.g. the last 500 bytes
+        let suffix: u64 = end.parse().ok()?;
+        if suffix == 0 || len == 0 {
+            return Some(Err(()));
+        }
+
+        return Some(Ok((len.saturating_sub(suffix), len - 1)));
+    }
+
+    let start: u64 = start.parse().ok()?;
+    let end = match end {
+        "" => u64::MAX,
+        end => end.parse().ok()?
+    };
+
+    if end < start {
+        return None;
+    }
+
+    if start >= len {
+        return Some(Err(()));
+    }
+
+    Some(Ok((start, end.min(len - 1))))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+
+    use super::*;
+
+    fn ranged(request: Request) -> Response<io::Take<Cursor<Vec<u8>>>> {
+        Response::bytes("0123456789", "text/plain").with_etag("abc").ranged(&request).unwrap()
+    }
+
+    fn body(mut response: Response<impl io::Read>) -> String {
+        let mut body = String::new();
+        response.body.read_to_string(&mut body).unwrap();
+        body
+    }
+
+    #[test]
+    fn parses_ranges() {
+        assert_eq!(parse_range("bytes=0-499", 1000), Some(Ok((0, 499))));
+        assert_eq!(parse_range("bytes = 10 - 20", 1000), Some(Ok((10, 20))));
+        assert_eq!(parse_range("BYTES=5-5", 1000), Some(Ok((5, 5))));
+    }
+
+    #[test]
+    fn parses_suffix_ranges() {
+        assert_eq!(parse_range("bytes=-500", 1000), Some(Ok((500, 999))));
+        assert_eq!(parse_range("bytes=-2000", 1000), Some(Ok((0, 999))));
+        assert_eq!(parse_range("bytes=-0", 1000), Some(Err(())));
+        assert_eq!(parse_range("bytes=-1", 0), Some(Err(())));
+    }
+
+    #[test]
+    fn parses_open_ended_ranges() {
+        assert_eq!(parse_range("bytes=900-", 1000), Some(Ok((900, 999))));
+        assert_eq!(parse_range("bytes=0-", 1), Some(Ok((0, 0))));
+    }
+
+    #[test]
+    fn ignores_inverted_and_invalid_ranges() {
+        assert_eq!(parse_range("bytes=5-1", 1000), None);
+        assert_eq!(parse_range("bytes=0-1,5-6", 1000), None);
+        assert_eq!(parse_range("items=0-1", 1000), None);
+        assert_eq!(parse_range("bytes=a-b", 1000), None);
+        assert_eq!(parse_range("bytes=-", 1000), None);
+        assert_eq!(parse_range("bytes=1", 1000), None);
+    }
+
+    #[test]
+    fn rejects_out_of_bounds_ranges() {
+        assert_eq!(parse_range("bytes=1000-", 1000), Some(Err(())));
+        assert_eq!(parse_range("bytes=1000-1001", 1000), Some(Err(())));
+        assert_eq!(parse_range("bytes=0-0", 0), Some(Err(())));
+        assert_eq!(parse_range("bytes=990-2000", 1000), Some(Ok((990, 999))));
+    }
+
+    #[test]
+    fn answers_range_requests() {
+        let response = ranged(Request::get("winit:///").with_header("Range", "bytes=2-4"));
+        assert_eq!(response.status, 206);
+        assert_eq!(response.headers.get("Content-Range"), Some("bytes 2-4/10"));
+        assert_eq!(response.content_length(), Some(3));
+        assert_eq!(body(response), "234");
+
+        let response = ranged(Request::get("winit:///").with_header("Range", "bytes=20-"));
+        assert_eq!(response.status, 416);
+        assert_eq!(response.headers.get("Content-Range"), Some("bytes */10"));
+        assert_eq!(body(response), "");
+
+        let response = ranged(Request::get("winit:///"));
+        assert_eq!(response.status, 200);
+        assert_eq!(response.headers.get("Accept-Ranges"), Some("bytes"));
+        assert_eq!(body(response), "0123456789");
+    }
+
+    #[test]
+    fn sends_everything_for_stale_if_range() {
+        let request = Request::get("winit:///").with_header("Range", "bytes=2-4");
+
+        let response = ranged(request.clone().with_header("If-Range", "\"abc\""));
+        assert_eq!(response.status, 206);
+
+        let response = ranged(request.clone().with_header("If-Range", "\"old\""));
+        assert_eq!(response.status, 200);
+        assert_eq!(body(response), "0123456789");
+
+        let response = ranged(request.with_header("If-Range", "W/\"abc\""));
+        assert_eq!(response.status, 200);
+    }
+
+    #[test]
+    fn ignores_ranges_for_head() {
+        let response = ranged(Request::new("HEAD", "winit:///").with_header("Range", "bytes=2-4"));
+        assert_eq!(response.status, 200);
+        assert_eq!(response.content_length(), Some(10));
+    }
+}
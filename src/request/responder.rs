@@ -0,0 +1,74 @@
+use std::io;
+
+use super::{CancellationToken, Response};
+
+type Completion<T> = Box<dyn FnOnce(Option<Response<T>>) + Send>;
+
+/// Completes a request, either immediately or later from any thread.
+///
+/// Dropping the responder without responding completes the request with `None`.
+pub struct Responder<T: io::Read> {
+    complete: Option<Completion<T>>,
+    cancellation: CancellationToken
+}
+
+impl<T: io::Read> Responder<T> {
+    pub(crate) fn new(
+        cancellation: CancellationToken,
+        complete: impl FnOnce(Option<Response<T>>) + Send + 'static
+    ) -> Self {
+        Self {
+            complete: Some(Box::new(complete)),
+            cancellation
+        }
+    }
+
+    /// Complete the request. `None` fails it as not found.
+    pub fn respond(mut self, response: Option<Response<T>>) {
+        if let Some(complete) = self.complete.take() {
+            complete(response);
+        }
+    }
+
+    /// Check whether the page has stopped waiting for the response. Always `false` on Linux,
+    /// where a request can only be cancelled once it has been responded to.
+    pub fn is_cancelled(&self) -> bool {
+        self.cancellation.is_cancelled()
+    }
+
+    /// Convert the eventual response, including a missing one
+    pub fn map_option<U: io::Read>(
+        self,
+        f: impl FnOnce(Option<Response<U>>) -> Option<Response<T>> + Send + 'static
+    ) -> Responder<U>
+    where
+        T: 'static
+    {
+        let cancellation = self.cancellation.clone();
+        Responder::new(cancellation, move |response| self.respond(f(response)))
+    }
+
+    /// Convert the eventual response, e.g. to add headers
+    pub fn map<U: io::Read>(self, f: impl FnOnce(Response<U>) -> Response<T> + Send + 'static) -> Responder<U>
+    where
+        T: 'static
+    {
+        self.map_option(|response| response.map(f))
+    }
+
+    /// Convert the body of the eventual response
+    pub fn map_body<U: io::Read>(self, f: impl FnOnce(U) -> T + Send + 'static) -> Responder<U>
+    where
+        T: 'static
+    {
+        self.map(|response| response.map_body(f))
+    }
+}
+
+impl<T: io::Read> Drop for Responder<T> {
+    fn drop(&mut self) {
+        if let Some(complete) = self.complete.take() {
+            complete(None);
+        }
+    }
+}
@@ -0,0 +1,326 @@
+use std::io;
+
+use super::{boxed, percent_decode, wait_for_response, BoxedBody, BoxedHandler, Request, RequestHandler, Responder, Response};
+
+/// Dispatches requests to handlers by method and path.
+///
+/// Patterns are matched segment by segment against the decoded path:
+///
+/// * `users` matches that segment literally
+/// * `:id` matches any single segment and captures it as `id`
+/// * `*` matches all remaining segments, and `*rest` also captures them as `rest`
+///
+/// Routes are tried in the order they were added. Captured parameters are available
+/// to handlers through `Request::param`. `HEAD` requests without a route of their own
+/// are answered by the matching `GET` route, without the body.
+///
+/// ```no_run
+/// # use winit_webview::request::{Request, Response, Router};
+/// let router = Router::new()
+///     .get("/api/users/:id", |request: Request| {
+///         let body = format!("{{\"id\": {:?}}}", request.param("id")?);
+///         Some(Response::bytes(body, "application/json"))
+///     })
+///     .mount("/admin", Router::new().get("/", |_| Some(Response::bytes("admin", "text/plain"))));
+/// ```
+#[derive(Default)]
+pub struct Router {
+    routes: Vec<Route>,
+    fallback: Option<BoxedHandler>
+}
+
+struct Route {
+    /// The method to match, or `None` for any
+    method: Option<String>,
+    pattern: Vec<Segment>,
+    handler: BoxedHandler
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Segment {
+    Literal(String),
+    Param(String),
+    Wildcard(Option<String>)
+}
+
+impl Router {
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    /// Add a route for the specified method and path pattern
+    pub fn route(self, method: impl Into<String>, pattern: &str, handler: impl RequestHandler) -> Self {
+        self.add(Some(method.into()), parse_pattern(pattern), handler)
+    }
+
+    /// Add a route for the specified path pattern, with any method
+    pub fn any(self, pattern: &str, handler: impl RequestHandler) -> Self {
+        self.add(None, parse_pattern(pattern), handler)
+    }
+
+    pub fn get(self, pattern: &str, handler: impl RequestHandler) -> Self {
+        self.route("GET", pattern, handler)
+    }
+
+    pub fn post(self, pattern: &str, handler: impl RequestHandler) -> Self {
+        self.route("POST", pattern, handler)
+    }
+
+    pub fn put(self, pattern: &str, handler: impl RequestHandler) -> Self {
+        self.route("PUT", pattern, handler)
+    }
+
+    pub fn delete(self, pattern: &str, handler: impl RequestHandler) -> Self {
+        self.route("DELETE", pattern, handler)
+    }
+
+    /// Hand every request under `prefix` to another handler, e.g. a nested `Router`.
+    ///
+    /// Nested routers match against the rest of the path, with the prefix removed.
+    pub fn mount(self, prefix: &str, handler: impl RequestHandler) -> Self {
+        let mut pattern = parse_pattern(prefix);
+        pattern.push(Segment::Wildcard(None));
+
+        self.add(None, pattern, handler)
+    }
+
+    /// Set the handler for requests that match no route. Without one, they fail as not found.
+    pub fn with_fallback(mut self, handler: impl RequestHandler) -> Self {
+        self.fallback = Some(boxed(handler));
+        self
+    }
+
+    fn add(mut self, method: Option<String>, pattern: Vec<Segment>, handler: impl RequestHandler) -> Self {
+        self.routes.push(Route {
+            method,
+            pattern,
+            handler: boxed(handler)
+        });
+        self
+    }
+}
+
+impl RequestHandler for Router {
+    type Read = BoxedBody;
+
+    fn handle(&mut self, request: Request) -> Option<Response<Self::Read>> {
+        wait_for_response(self, request)
+    }
+
+    fn handle_async(&mut self, mut request: Request, responder: Responder<Self::Read>) {
+        // Left in place for the fallback, which sees the same path as this router
+        let path = request.route_path.clone().unwrap_or_else(|| request.raw_path().to_owned());
+        let segments: Vec<&str> = path.split('/').filter(|segment| !segment.is_empty()).collect();
+
+        // HEAD goes to a route of its own if there is one, and otherwise gets the headers of GET, without the body
+        let is_head = request.method.eq_ignore_ascii_case("HEAD");
+        let has_head_route = is_head
+            && self.routes.iter().any(|route| {
+                route.method.as_ref().is_some_and(|method| method.eq_ignore_ascii_case("HEAD"))
+                    && match_pattern(&route.pattern, &segments).is_some()
+            });
+
+        let mut allowed = Vec::new();
+        for route in &mut self.routes {
+            let captures = match match_pattern(&route.pattern, &segments) {
+                Some(captures) => captures,
+                None => continue
+            };
+
+            let mut without_body = false;
+            if let Some(method) = &route.method {
+                if !allowed.contains(method) {
+                    allowed.push(method.clone());
+                }
+
+                if is_head && !has_head_route && method.eq_ignore_ascii_case("GET") {
+                    without_body = true;
+                } else if !method.eq_ignore_ascii_case(&request.method) {
+                    continue;
+                }
+            }
+
+            request.params.extend(captures.params);
+            request.route_path = Some(captures.rest);
+            let responder = if without_body {
+                responder.map(|response| response.map_body(|_| Box::new(io::empty()) as BoxedBody))
+            } else {
+                responder
+            };
+
+            return (route.handler)(request, responder);
+        }
+
+        if let Some(fallback) = &mut self.fallback {
+            return fallback(request, responder);
+        }
+
+        if allowed.iter().any(|method| method.eq_ignore_ascii_case("GET")) {
+            allowed.push("HEAD".to_owned());
+        }
+
+        if allowed.is_empty() {
+            responder.respond(None);
+        } else {
+            // The path exists, but not for this method
+            let response = Response::<io::Empty>::empty(405).with_header("Allow", allowed.join(", "));
+            responder.respond(Some(response.boxed()));
+        }
+    }
+}
+
+fn parse_pattern(pattern: &str) -> Vec<Segment> {
+    pattern
+        .split('/')
+        .filter(|segment| !segment.is_empty())
+        .map(|segment| {
+            if let Some(name) = segment.strip_prefix(':') {
+                Segment::Param(name.to_owned())
+            } else if let Some(name) = segment.strip_prefix('*') {
+                Segment::Wildcard(Some(name.to_owned()).filter(|name| !name.is_empty()))
+            } else {
+                Segment::Literal(segment.to_owned())
+            }
+        })
+        .collect()
+}
+
+struct Captures {
+    params: Vec<(String, String)>,
+    /// The still-encoded path left over after a wildcard, for nested handlers
+    rest: String
+}
+
+fn match_pattern(pattern: &[Segment], segments: &[&str]) -> Option<Captures> {
+    let mut params = Vec::new();
+
+    for (index, part) in pattern.iter().enumerate() {
+        match part {
+            Segment::Wildcard(name) => {
+                let rest = format!("/{}", segments.get(index..).unwrap_or_default().join("/"));
+                if let Some(name) = name {
+                    params.push((name.clone(), percent_decode(&rest[1..], false).into_owned()));
+                }
+
+                return Some(Captures { params, rest });
+            }
+            Segment::Literal(literal) => {
+                if percent_decode(segments.get(index)?, false) != literal.as_str() {
+                    return None;
+                }
+            }
+            Segment::Param(name) => {
+                params.push((name.clone(), percent_decode(segments.get(index)?, false).into_owned()));
+            }
+        }
+    }
+
+    if segments.len() != pattern.len() {
+        return None;
+    }
+
+    Some(Captures {
+        params,
+        rest: "/".to_owned()
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{io::Read, sync::mpsc, thread};
+
+    use super::*;
+
+    fn send(router: &mut Router, request: Request) -> Option<String> {
+        let mut body = String::new();
+        router.handle(request)?.body.read_to_string(&mut body).unwrap();
+        Some(body)
+    }
+
+    fn echo_path(request: Request) -> Option<Response<io::Cursor<Vec<u8>>>> {
+        Some(Response::bytes(request.relative_path().into_owned(), "text/plain"))
+    }
+
+    #[test]
+    fn captures_params() {
+        let mut router = Router::new().get("/users/:id/*rest", |request: Request| {
+            let body = format!("{} {}", request.param("id")?, request.param("rest")?);
+            Some(Response::bytes(body, "text/plain"))
+        });
+
+        assert_eq!(send(&mut router, Request::get("winit:///users/a%20b/x/y")).as_deref(), Some("a b x/y"));
+        assert_eq!(send(&mut router, Request::get("winit:///users")), None);
+    }
+
+    #[test]
+    fn nested_routes_see_the_path_below_the_mount() {
+        let mut router = Router::new().mount("/app", Router::new().get("/:name", |request: Request| {
+            Some(Response::bytes(request.param("name")?, "text/plain"))
+        }));
+
+        assert_eq!(send(&mut router, Request::get("winit:///app/x")).as_deref(), Some("x"));
+        assert_eq!(send(&mut router, Request::get("winit:///x")), None);
+    }
+
+    #[test]
+    fn nested_fallbacks_see_the_path_below_the_mount() {
+        let mut router = Router::new()
+            .mount("/app", Router::new().get("/index", echo_path).with_fallback(echo_path))
+            .with_fallback(echo_path);
+
+        assert_eq!(send(&mut router, Request::get("winit:///app/assets/a.js")).as_deref(), Some("/assets/a.js"));
+        assert_eq!(send(&mut router, Request::get("winit:///other")).as_deref(), Some("/other"));
+    }
+
+    #[test]
+    fn waits_for_handlers_that_respond_later() {
+        struct Later;
+
+        impl RequestHandler for Later {
+            type Read = io::Cursor<Vec<u8>>;
+
+            fn handle_async(&mut self, _request: Request, responder: Responder<Self::Read>) {
+                thread::spawn(move || responder.respond(Some(Response::bytes("later", "text/plain"))));
+            }
+        }
+
+        let mut router = Router::new().get("/later", Later);
+        assert_eq!(send(&mut router, Request::get("winit:///later")).as_deref(), Some("later"));
+    }
+
+    #[test]
+    fn answers_head_with_get_routes() {
+        let (sender, receiver) = mpsc::channel();
+        let mut router = Router::new()
+            .get("/page", move |request: Request| {
+                sender.send(request.method.clone()).unwrap();
+                Some(Response::bytes("page", "text/html"))
+            })
+            .post("/form", |_| Some(Response::bytes("saved", "text/plain")));
+
+        let response = router.handle(Request::new("HEAD", "winit:///page")).unwrap();
+        assert_eq!(response.status, 200);
+        assert_eq!(response.content_length(), Some(4));
+        assert_eq!(send(&mut router, Request::new("HEAD", "winit:///page")).as_deref(), Some(""));
+        assert_eq!(receiver.try_iter().collect::<Vec<_>>(), ["HEAD", "HEAD"]);
+
+        let response = router.handle(Request::new("HEAD", "winit:///form")).unwrap();
+        assert_eq!(response.status, 405);
+        assert_eq!(response.headers.get("Allow"), Some("POST"));
+
+        let response = router.handle(Request::new("DELETE", "winit:///page")).unwrap();
+        assert_eq!(response.headers.get("Allow"), Some("GET, HEAD"));
+    }
+
+    #[test]
+    fn prefers_head_routes_to_get_routes() {
+        let mut router = Router::new()
+            .get("/page", |_| Some(Response::bytes("get", "text/plain")))
+            .route("HEAD", "/page", |_| Some(Response::bytes("head", "text/plain")))
+            .get("/other", |_| Some(Response::bytes("get", "text/plain")))
+            .any("/*", |_| Some(Response::bytes("any", "text/plain")));
+
+        assert_eq!(send(&mut router, Request::new("HEAD", "winit:///page")).as_deref(), Some("head"));
+        assert_eq!(router.handle(Request::new("HEAD", "winit:///other")).unwrap().content_length(), Some(3));
+    }
+}
@@ -0,0 +1,320 @@
+use std::{
+    fs, io,
+    path::{Path, PathBuf},
+    time::UNIX_EPOCH
+};
+
+use super::{BoxedBody, Request, RequestHandler, Response};
+
+/// Serves files from a directory on disk.
+///
+/// Requests are confined to the root: `..` segments are refused with `403 Forbidden`,
+/// as are symlinks that resolve outside of it. Directories are served through their
+/// index file, and missing files get `404 Not Found`. Responses carry `ETag` and `Last-Modified`
+/// validators, and `Range` requests are answered with the requested part of the file.
+pub struct StaticDir {
+    root: PathBuf,
+    index: String,
+    fallback: Option<String>
+}
+
+impl StaticDir {
+    pub fn new(root: impl Into<PathBuf>) -> Self {
+        Self {
+            root: root.into(),
+            index: "index.html".to_owned(),
+            fallback: None
+        }
+    }
+
+    /// Set the file served for directories. Defaults to `index.html`.
+    pub fn with_index(mut self, index: impl Into<String>) -> Self {
+        self.index = index.into();
+        self
+    }
+
+    /// Serve the specified file, relative to the root, for missing paths without a file extension.
+    ///
+    /// This lets single-page apps handle their own client-side routes.
+    pub fn with_spa_fallback(mut self, entry_point: impl Into<String>) -> Self {
+        self.fallback = Some(entry_point.into());
+        self
+    }
+
+    /// Find the file to serve for a decoded request path
+    fn resolve(&self, path: &str) -> Result<(PathBuf, fs::File, fs::Metadata), u16> {
+        let root = self.root.canonicalize().map_err(|_| 404u16)?;
+
+        let segments = match safe_segments(path) {
+            Some(segments) => segments,
+            None => return Err(403)
+        };
+
+        let mut candidate = root.clone();
+        candidate.extend(&segments);
+
+        let mut result = open_confined(&root, &candidate, &self.index);
+        if matches!(result, Err(404)) {
+            let is_route = segments.last().map_or(true, |name| Path::new(name).extension().is_none());
+            if let Some(fallback) = self.fallback.as_deref().filter(|_| is_route) {
+                result = match safe_segments(fallback) {
+                    Some(fallback) => open_confined(&root, &root.join(fallback.join("/")), &self.index),
+                    None => Err(403)
+                };
+            }
+        }
+
+        result
+    }
+}
+
+impl RequestHandler for StaticDir {
+    type Read = BoxedBody;
+
+    fn handle(&mut self, request: Request) -> Option<Response<Self::Read>> {
+        Some(serve_file(&request, || {
+            let (path, file, metadata) = self.resolve(&request.relative_path())?;
+
+            let mut response = Response::ok(file, mime_type(&path)).with_content_length(metadata.len());
+            if let Ok(modified) = metadata.modified() {
+                // Like most web servers, derive the tag from the modification time and size
+                let mtime = modified.duration_since(UNIX_EPOCH).unwrap_or_default();
+                response = response
+                    .with_etag(format!("{:x}-{:x}-{:x}", mtime.as_secs(), mtime.subsec_nanos(), metadata.len()))
+                    .with_last_modified(modified);
+            }
+
+            Ok(response)
+        }))
+    }
+}
+
+/// Answer a request to one of the file handlers.
+///
+/// Only `GET` and `HEAD` are allowed. `open` finds the file and builds its response, or fails with
+/// a status. `Range` requests get the requested part of the file, which lets media elements scrub
+/// through large files, and `HEAD` gets the headers of `GET` without the body.
+pub(crate) fn serve_file<R: io::Read + io::Seek + Send + 'static>(
+    request: &Request,
+    open: impl FnOnce() -> Result<Response<R>, u16>
+) -> Response<BoxedBody> {
+    serve_stream(request, || open()?.ranged(request).map_err(|_| 500))
+}
+
+/// Like `serve_file`, for bodies that can't seek, which are always sent whole
+pub(crate) fn serve_stream<R: io::Read + Send + 'static>(
+    request: &Request,
+    open: impl FnOnce() -> Result<Response<R>, u16>
+) -> Response<BoxedBody> {
+    if !matches!(request.method.as_str(), "GET" | "HEAD") {
+        return Response::<io::Empty>::empty(405).with_header("Allow", "GET, HEAD").boxed();
+    }
+
+    match open() {
+        Ok(response) if request.method == "HEAD" => response.map_body(|_| io::empty()).boxed(),
+        Ok(response) => response.boxed(),
+        Err(status) => Response::<io::Empty>::empty(status).boxed()
+    }
+}
+
+/// Guess the MIME type of a file from its extension
+pub(crate) fn mime_type(path: impl AsRef<Path>) -> &'static str {
+    mime_guess::from_path(path).first_raw().unwrap_or("application/octet-stream")
+}
+
+/// Resolve a decoded request path within a virtual tree of files, with the same
+/// index and fallback behavior as `StaticDir`
+pub(crate) fn resolve_virtual(
+    path: &str,
+    index: &str,
+    fallback: Option<&str>,
+    exists: impl Fn(&str) -> bool
+) -> Result<String, u16> {
+    let segments = safe_segments(path).ok_or(403u16)?;
+
+    let path = segments.join("/");
+    let index = if path.is_empty() { index.to_owned() } else { format!("{}/{}", path, index) };
+    for candidate in [path, index] {
+        if !candidate.is_empty() && exists(&candidate) {
+            return Ok(candidate);
+        }
+    }
+
+    let is_route = segments.last().map_or(true, |name| Path::new(name).extension().is_none());
+    match fallback.filter(|_| is_route) {
+        Some(fallback) => {
+            let fallback = safe_segments(fallback).ok_or(403u16)?.join("/");
+            if exists(&fallback) {
+                Ok(fallback)
+            } else {
+                Err(404)
+            }
+        }
+        None => Err(404)
+    }
+}
+
+/// Split a decoded path into segments, or `None` if it tries to leave the root
+pub(crate) fn safe_segments(path: &str) -> Option<Vec<&str>> {
+    let mut segments = Vec::new();
+    for segment in path.split('/') {
+        match segment {
+            "" | "." => {}
+            ".." => return None,
+            _ if segment.contains(['\\', '\0']) || Path::new(segment).has_root() => return None,
+            _ => segments.push(segment)
+        }
+    }
+
+    Some(segments)
+}
+
+/// Open a file, or the index file of a directory, as long as it resolves inside the root
+fn open_confined(root: &Path, path: &Path, index: &str) -> Result<(PathBuf, fs::File, fs::Metadata), u16> {
+    let mut path = path.canonicalize().map_err(status_for)?;
+    if !path.starts_with(root) {
+        return Err(403);
+    }
+
+    if path.is_dir() {
+        path = path.join(index).canonicalize().map_err(status_for)?;
+        if !path.starts_with(root) {
+            return Err(403);
+        }
+    }
+
+    let file = fs::File::open(&path).map_err(status_for)?;
+    let metadata = file.metadata().map_err(status_for)?;
+    if !metadata.is_file() {
+        return Err(404);
+    }
+
+    Ok((path, file, metadata))
+}
+
+fn status_for(err: io::Error) -> u16 {
+    match err.kind() {
+        io::ErrorKind::PermissionDenied => 403,
+        _ => 404
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{env, io::Read, process};
+
+    use super::*;
+
+    /// Create an empty directory to serve, unique to the test
+    fn temp_dir(name: &str) -> PathBuf {
+        let dir = env::temp_dir().join(format!("winit-webview-{}-{}", name, process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    fn status(handler: &mut StaticDir, url: &str) -> u16 {
+        handler.handle(Request::get(url)).unwrap().status
+    }
+
+    #[test]
+    fn splits_safe_segments() {
+        assert_eq!(safe_segments("/a/./b//c"), Some(vec!["a", "b", "c"]));
+        assert_eq!(safe_segments("/"), Some(vec![]));
+        assert_eq!(safe_segments("/a/../b"), None);
+        assert_eq!(safe_segments(".."), None);
+        assert_eq!(safe_segments("/a\\..\\b"), None);
+        assert_eq!(safe_segments("/a\0b"), None);
+    }
+
+    #[test]
+    fn serves_files_and_indexes() {
+        let root = temp_dir("serve");
+        fs::create_dir(root.join("docs")).unwrap();
+        fs::write(root.join("docs/index.html"), "<p>Docs</p>").unwrap();
+        fs::write(root.join("app.js"), "run()").unwrap();
+        let mut handler = StaticDir::new(&root);
+
+        let mut response = handler.handle(Request::get("winit:///docs/")).unwrap();
+        let mut body = String::new();
+        response.body.read_to_string(&mut body).unwrap();
+        assert_eq!(body, "<p>Docs</p>");
+        assert_eq!(response.mime_type, "text/html");
+
+        assert_eq!(status(&mut handler, "winit:///app.js"), 200);
+        assert_eq!(status(&mut handler, "winit:///missing.js"), 404);
+    }
+
+    fn serve(request: Request) -> Response<BoxedBody> {
+        serve_file(&request, || Ok(Response::ok(io::Cursor::new("run()"), "text/javascript").with_etag("\"1\"")))
+    }
+
+    #[test]
+    fn only_serves_get_and_head() {
+        let response = serve(Request::new("POST", "winit:///app.js"));
+        assert_eq!(response.status, 405);
+        assert_eq!(response.headers.get("Allow"), Some("GET, HEAD"));
+
+        let response = serve_file(&Request::get("winit:///app.js"), || Err::<Response<io::Empty>, _>(404));
+        assert_eq!(response.status, 404);
+    }
+
+    #[test]
+    fn serves_ranges_of_files() {
+        let mut response = serve(Request::get("winit:///app.js").with_header("Range", "bytes=1-2"));
+        let mut body = String::new();
+        response.body.read_to_string(&mut body).unwrap();
+
+        assert_eq!(response.status, 206);
+        assert_eq!(body, "un");
+    }
+
+    #[test]
+    fn head_has_the_headers_of_get() {
+        let get = serve(Request::get("winit:///app.js"));
+        let mut head = serve(Request::new("HEAD", "winit:///app.js").with_header("Range", "bytes=0-1"));
+
+        assert_eq!(head.status, 200);
+        assert_eq!(head.header_fields(), get.header_fields());
+        assert_eq!(head.content_length(), Some(5));
+        assert_eq!(head.headers.get("Accept-Ranges"), Some("bytes"));
+        assert_eq!(head.body.read(&mut [0; 8]).unwrap(), 0);
+    }
+
+    #[test]
+    fn refuses_traversal() {
+        let root = temp_dir("traversal");
+        fs::create_dir(root.join("public")).unwrap();
+        fs::write(root.join("secret.txt"), "secret").unwrap();
+        fs::write(root.join("public/index.html"), "index").unwrap();
+        let mut handler = StaticDir::new(root.join("public"));
+
+        assert_eq!(status(&mut handler, "winit:///../secret.txt"), 403);
+        assert_eq!(status(&mut handler, "winit:///%2e%2e/secret.txt"), 403);
+        assert_eq!(status(&mut handler, "winit:///a/%2E%2E/%2e%2e/secret.txt"), 403);
+        assert_eq!(status(&mut handler, "winit:///..%2fsecret.txt"), 403);
+        assert_eq!(status(&mut handler, "winit:///..%5csecret.txt"), 403);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn refuses_symlinks_out_of_the_root() {
+        use std::os::unix::fs::symlink;
+
+        let root = temp_dir("symlinks");
+        fs::create_dir(root.join("public")).unwrap();
+        fs::create_dir(root.join("outside")).unwrap();
+        fs::write(root.join("secret.txt"), "secret").unwrap();
+        fs::write(root.join("outside/index.html"), "outside").unwrap();
+        fs::write(root.join("public/page.html"), "page").unwrap();
+        symlink(root.join("secret.txt"), root.join("public/secret.txt")).unwrap();
+        symlink(root.join("outside"), root.join("public/outside")).unwrap();
+        symlink(root.join("public/page.html"), root.join("public/alias.html")).unwrap();
+        let mut handler = StaticDir::new(root.join("public"));
+
+        assert_eq!(status(&mut handler, "winit:///secret.txt"), 403);
+        assert_eq!(status(&mut handler, "winit:///outside/"), 403);
+        assert_eq!(status(&mut handler, "winit:///outside/index.html"), 403);
+        assert_eq!(status(&mut handler, "winit:///alias.html"), 200);
+    }
+}
@@ -0,0 +1,119 @@
+use std::{
+    error, fmt,
+    future::Future,
+    pin::Pin,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc, Mutex
+    },
+    task::{Context, Poll, Waker}
+};
+
+/// The outcome of a script run with `WebView::evaluate_with_result`: the value of its last
+/// statement as JSON text, or the error it threw. `undefined` and values without a JSON
+/// representation, such as functions, become `null`. Dates become strings on Linux, and `null` on macOS.
+pub type ScriptResult = Result<String, ScriptError>;
+
+/// The type-erased completion of a script
+pub(crate) type ScriptCallback = Box<dyn FnOnce(ScriptResult)>;
+
+/// An error thrown by a script, or raised while running it
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ScriptError {
+    pub message: String,
+    /// The line the error was thrown from, when the engine reports it
+    pub line: Option<u32>
+}
+
+impl fmt::Display for ScriptError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.line {
+            Some(line) => write!(f, "{} (line {})", self.message, line),
+            None => write!(f, "{}", self.message)
+        }
+    }
+}
+
+impl error::Error for ScriptError {}
+
+/// Get a new id for `WebView::evaluate_with_id`, unique across views
+pub(crate) fn next_id() -> u64 {
+    static NEXT_ID: AtomicU64 = AtomicU64::new(1);
+    NEXT_ID.fetch_add(1, Ordering::Relaxed)
+}
+
+/// Holds back scripts that deliver events to the page while it's loading, as its listeners aren't registered yet
+#[derive(Debug, Default)]
+pub(crate) struct EventQueue {
+    loaded: bool,
+    pending: Vec<String>
+}
+
+impl EventQueue {
+    /// Queue a script while the page is loading, or hand it back to run now
+    pub(crate) fn push(&mut self, script: String) -> Option<String> {
+        if self.loaded {
+            Some(script)
+        } else {
+            self.pending.push(script);
+            None
+        }
+    }
+
+    /// Mark a page as loading. Returns whether a page that had loaded is being left.
+    pub(crate) fn start_loading(&mut self) -> bool {
+        std::mem::replace(&mut self.loaded, false)
+    }
+
+    /// Mark the load as over, whether it finished or failed, taking the scripts to run now, oldest first
+    pub(crate) fn finish_loading(&mut self) -> Vec<String> {
+        self.loaded = true;
+        std::mem::take(&mut self.pending)
+    }
+}
+
+/// The result of a script that may still be running. Created by `WebView::evaluate_async`.
+///
+/// Results are delivered while the event loop runs, so don't block the main thread on this.
+pub struct ScriptFuture {
+    state: Arc<Mutex<FutureState>>
+}
+
+#[derive(Default)]
+struct FutureState {
+    result: Option<ScriptResult>,
+    waker: Option<Waker>
+}
+
+impl ScriptFuture {
+    /// Create a future, along with the callback that completes it
+    pub(crate) fn new() -> (Self, ScriptCallback) {
+        let state = Arc::new(Mutex::new(FutureState::default()));
+        let future = ScriptFuture { state: state.clone() };
+
+        let complete = Box::new(move |result| {
+            let mut state = state.lock().unwrap();
+            state.result = Some(result);
+            if let Some(waker) = state.waker.take() {
+                waker.wake();
+            }
+        });
+
+        (future, complete)
+    }
+}
+
+impl Future for ScriptFuture {
+    type Output = ScriptResult;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context) -> Poll<Self::Output> {
+        let mut state = self.state.lock().unwrap();
+        match state.result.take() {
+            Some(result) => Poll::Ready(result),
+            None => {
+                state.waker = Some(cx.waker().clone());
+                Poll::Pending
+            }
+        }
+    }
+}
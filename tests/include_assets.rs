@@ -0,0 +1,44 @@
+use std::io::Read;
+
+use winit_webview::{
+    include_assets,
+    request::{Embedded, EmbeddedDir, Request, RequestHandler}
+};
+
+static ASSETS: EmbeddedDir = include_assets!("tests/assets");
+
+#[test]
+fn embeds_files_sorted_by_path() {
+    let paths: Vec<_> = ASSETS.files().iter().map(|file| file.path).collect();
+
+    // The precompressed copy of `js/app.js` is left out
+    assert_eq!(paths, ["index.html", "js/app.js", "style.css"]);
+}
+
+#[test]
+fn computes_mime_types_and_etags() {
+    let script = ASSETS.get("/js/app.js").unwrap();
+    assert_eq!(script.contents, b"document.title = \"Assets\";\n");
+    assert_eq!(script.mime_type, "text/javascript");
+    assert_eq!(script.etag, "\"8ef8545553eca487\"");
+
+    assert_eq!(ASSETS.get("index.html").unwrap().mime_type, "text/html");
+    assert_eq!(ASSETS.get("style.css").unwrap().mime_type, "text/css");
+    assert!(ASSETS.get("js/app.js.gz").is_none());
+}
+
+#[test]
+fn serves_embedded_files() {
+    let mut handler = Embedded::new(&ASSETS);
+
+    let mut response = handler.handle(Request::get("winit:///")).unwrap();
+    let mut body = String::new();
+    response.body.read_to_string(&mut body).unwrap();
+    assert_eq!(response.mime_type, "text/html");
+    assert!(body.contains("js/app.js"));
+
+    let response = handler.handle(Request::get("winit:///js/app.js")).unwrap();
+    assert_eq!(response.headers.get("ETag"), Some("\"8ef8545553eca487\""));
+    assert_eq!(response.headers.get("Content-Encoding"), None);
+    assert_eq!(handler.handle(Request::get("winit:///js/app.js.gz")).unwrap().status, 404);
+}
@@ -0,0 +1,491 @@
+#![cfg(feature = "mock")]
+
+use std::{
+    cell::RefCell,
+    io::{self, Read},
+    rc::Rc,
+    sync::mpsc,
+    thread
+};
+
+use winit_webview::{
+    platform::mock::*,
+    request::{Request, RequestHandler, Responder, Response},
+    *
+};
+
+fn read_body(response: request::Response<request::BoxedBody>) -> String {
+    let mut body = String::new();
+    let mut response = response;
+    response.body.read_to_string(&mut body).unwrap();
+    body
+}
+
+#[test]
+fn records_navigations_and_scripts() {
+    let mut web_view = WebViewBuilder::new().with_init_script("window.ready = true;").build_headless(|_| {});
+
+    web_view.navigate(NavigationTarget::Url("winit:///index.html"));
+    web_view.navigate(NavigationTarget::Html("<p>Hi</p>"));
+    web_view.evaluate("document.title");
+
+    assert_eq!(web_view.navigations(), [
+        Navigation::Url("winit:///index.html".to_owned()),
+        Navigation::Html("<p>Hi</p>".to_owned())
+    ]);
+    assert_eq!(web_view.evaluated_scripts(), ["document.title"]);
+    assert_eq!(web_view.init_scripts(), ["window.ready = true;"]);
+}
+
+#[test]
+fn reports_navigation_events() {
+    let (sender, receiver) = mpsc::channel();
+    let mut web_view = WebViewBuilder::new().build_headless(sender);
+
+    web_view.navigate(NavigationTarget::Url("winit:///index.html"));
+    web_view.complete_navigation();
+
+    let events: Vec<_> = receiver.try_iter().collect();
+    let info = match &events[..] {
+        [Event::Navigation(NavigationEvent::Start(start)), Event::Navigation(NavigationEvent::Commit(commit)), Event::Navigation(NavigationEvent::Finish(finish))] => {
+            assert_eq!(start, commit);
+            assert_eq!(start, finish);
+            start.clone()
+        }
+        events => panic!("unexpected events {:?}", events)
+    };
+    assert_eq!(info.url, "winit:///index.html");
+    assert!(!info.same_document);
+
+    web_view.navigate_within_document("winit:///index.html#details");
+    match receiver.try_iter().last() {
+        Some(Event::Navigation(NavigationEvent::Finish(finish))) => {
+            assert!(finish.same_document);
+            assert_ne!(finish.id, info.id);
+        }
+        event => panic!("unexpected event {:?}", event)
+    }
+}
+
+#[test]
+fn completes_scripts_in_order() {
+    let (sender, receiver) = mpsc::channel();
+    let mut web_view = WebViewBuilder::new().build_headless(sender);
+
+    let result = Rc::new(RefCell::new(None));
+    let callback_result = result.clone();
+    web_view.evaluate_with_result("1 + 1", move |value| *callback_result.borrow_mut() = Some(value));
+    let id = web_view.evaluate_with_id("document.title");
+
+    assert!(web_view.complete_script(Ok("2".to_owned())));
+    assert_eq!(*result.borrow(), Some(Ok("2".to_owned())));
+
+    let error = ScriptError { message: "ReferenceError".to_owned(), line: Some(1) };
+    assert!(web_view.complete_script(Err(error.clone())));
+    match receiver.try_recv() {
+        Ok(Event::ScriptResult { id: result_id, result }) => {
+            assert_eq!(result_id, id);
+            assert_eq!(result, Err(error));
+        }
+        event => panic!("unexpected event {:?}", event)
+    }
+
+    assert!(!web_view.complete_script(Ok("null".to_owned())));
+}
+
+#[test]
+fn serves_requests_per_scheme() {
+    let mut web_view = WebViewBuilder::with_request_handler(|_: Request| Some(Response::bytes("winit", "text/plain")))
+        .with_scheme("app", |request: Request| Some(Response::bytes(request.path().into_owned(), "text/plain")))
+        .build_headless(|_| {});
+
+    assert_eq!(read_body(web_view.request("winit:///x").unwrap()), "winit");
+    assert_eq!(read_body(web_view.request("app://host/a%20b").unwrap()), "/a b");
+    assert!(web_view.request("media:///x").is_none());
+
+    let response = web_view
+        .send_request(Request::new("POST", "app://host/form").with_body("name=value"))
+        .unwrap();
+    assert_eq!(response.status, 200);
+}
+
+/// Answers each request from another thread, with whether it had been cancelled by then
+struct ReportCancellation;
+
+impl RequestHandler for ReportCancellation {
+    type Read = io::Cursor<Vec<u8>>;
+
+    fn handle_async(&mut self, request: Request, responder: Responder<Self::Read>) {
+        let cancellation = request.cancellation.clone();
+        thread::spawn(move || {
+            if request.header("X-Wait").is_some() {
+                let (sender, receiver) = mpsc::channel();
+                cancellation.on_cancel(move || sender.send(()).unwrap());
+                receiver.recv().unwrap();
+            }
+
+            let body = if responder.is_cancelled() { "cancelled" } else { "answered" };
+            responder.respond(Some(Response::bytes(body, "text/plain")));
+        });
+    }
+}
+
+#[test]
+fn answers_requests_from_other_threads() {
+    let mut web_view = WebViewBuilder::with_request_handler(ReportCancellation).build_headless(|_| {});
+
+    assert_eq!(read_body(web_view.request("winit:///slow").unwrap()), "answered");
+}
+
+#[test]
+fn lets_handlers_see_cancellation() {
+    let mut web_view = WebViewBuilder::with_request_handler(ReportCancellation).build_headless(|_| {});
+
+    let request = Request::get("winit:///slow").with_header("X-Wait", "1");
+    let cancellation = request.cancellation.clone();
+    let canceller = thread::spawn(move || cancellation.cancel());
+
+    assert_eq!(read_body(web_view.send_request(request).unwrap()), "cancelled");
+    canceller.join().unwrap();
+}
+
+#[test]
+fn fails_requests_whose_responder_is_dropped() {
+    struct DropResponder;
+
+    impl RequestHandler for DropResponder {
+        type Read = io::Empty;
+
+        fn handle_async(&mut self, _request: Request, responder: Responder<Self::Read>) {
+            thread::spawn(move || drop(responder));
+        }
+    }
+
+    let mut web_view = WebViewBuilder::with_request_handler(DropResponder).build_headless(|_| {});
+    assert!(web_view.request("winit:///dropped").is_none());
+}
+
+#[test]
+fn delivers_channel_messages() {
+    let (sender, receiver) = mpsc::channel();
+    let mut web_view = WebViewBuilder::new().with_message_channel("telemetry").build_headless(sender);
+
+    assert!(web_view.post_to_channel("telemetry", "started"));
+    assert!(!web_view.post_to_channel("editor", "ignored"));
+
+    match receiver.try_recv() {
+        Ok(Event::ChannelMessage { channel, message, .. }) => {
+            assert_eq!(channel, "telemetry");
+            assert_eq!(message, "started");
+        }
+        event => panic!("unexpected event {:?}", event)
+    }
+    assert!(receiver.try_recv().is_err());
+}
+
+#[cfg(feature = "serde")]
+#[derive(Debug, PartialEq, serde::Deserialize)]
+#[serde(tag = "kind", rename_all = "lowercase")]
+enum Command {
+    Save { path: String },
+    Quit
+}
+
+#[cfg(feature = "serde")]
+#[test]
+fn decodes_json_messages() {
+    let (sender, receiver) = mpsc::channel();
+    let mut web_view = WebViewBuilder::new().build_headless(sender);
+
+    web_view.post_json(r#"{"kind": "save", "path": "notes.txt"}"#);
+    web_view.post_json("[1, 2.5, null]");
+    web_view.post_json("{\"kind\": ");
+
+    match receiver.try_recv() {
+        Ok(Event::Json(value, None)) => assert_eq!(value, serde_json::json!({ "kind": "save", "path": "notes.txt" })),
+        event => panic!("unexpected event {:?}", event)
+    }
+    match receiver.try_recv() {
+        Ok(Event::Json(value, None)) => assert_eq!(value, serde_json::json!([1, 2.5, null])),
+        event => panic!("unexpected event {:?}", event)
+    }
+    match receiver.try_recv() {
+        Ok(Event::MessageError(error)) => assert_eq!(error.body.as_deref(), Some("{\"kind\": ")),
+        event => panic!("unexpected event {:?}", event)
+    }
+    assert!(receiver.try_recv().is_err());
+}
+
+#[cfg(feature = "serde")]
+#[test]
+fn decodes_typed_messages() {
+    let (sender, receiver) = mpsc::channel();
+    let handler = ipc::Typed::new(move |event: ipc::TypedEvent<Command>| sender.send(event).unwrap());
+    let mut web_view = WebViewBuilder::new().build_headless(handler);
+
+    web_view.post_json(r#"{"kind": "save", "path": "notes.txt"}"#);
+    web_view.post_json(r#"{"kind": "quit"}"#);
+    web_view.post_json(r#"{"kind": "delete"}"#);
+    web_view.post_json("42");
+
+    match receiver.try_recv() {
+        Ok(ipc::TypedEvent::Message(command, None)) => assert_eq!(command, Command::Save { path: "notes.txt".to_owned() }),
+        event => panic!("unexpected event {:?}", event)
+    }
+    match receiver.try_recv() {
+        Ok(ipc::TypedEvent::Message(command, None)) => assert_eq!(command, Command::Quit),
+        event => panic!("unexpected event {:?}", event)
+    }
+    for body in [r#"{"kind":"delete"}"#, "42"] {
+        match receiver.try_recv() {
+            Ok(ipc::TypedEvent::Event(Event::MessageError(error))) => assert_eq!(error.body.as_deref(), Some(body)),
+            event => panic!("unexpected event {:?}", event)
+        }
+    }
+    assert!(receiver.try_recv().is_err());
+}
+
+#[cfg(feature = "serde")]
+#[test]
+fn answers_commands() {
+    let mut web_view = WebViewBuilder::new()
+        .with_command("add", |(a, b): (i64, i64)| Ok::<_, String>(a + b))
+        .with_command("divide", |(a, b): (i64, i64)| if b == 0 { Err("Division by zero") } else { Ok(a / b) })
+        .with_async_command("greet", |name: String, reply: ipc::Reply<String>| {
+            thread::spawn(move || reply.resolve(format!("Hello, {}", name)));
+        })
+        .build_headless(|_| {});
+
+    assert_eq!(web_view.invoke("add", serde_json::json!([1, 2])), Ok(serde_json::json!(3)));
+    assert_eq!(web_view.invoke("divide", serde_json::json!([1, 0])), Err("Division by zero".to_owned()));
+    assert_eq!(web_view.invoke("greet", serde_json::json!("page")), Ok(serde_json::json!("Hello, page")));
+}
+
+#[cfg(feature = "serde")]
+#[test]
+fn rejects_calls_that_get_no_answer() {
+    let mut web_view = WebViewBuilder::new()
+        .with_command("add", |(a, b): (i64, i64)| Ok::<_, String>(a + b))
+        .with_async_command("forget", |_: (), reply: ipc::Reply<()>| drop(reply))
+        .build_headless(|_| {});
+
+    let error = web_view.invoke("add", serde_json::json!("one")).unwrap_err();
+    assert!(error.starts_with("Invalid arguments for `add`: "), "{}", error);
+    assert_eq!(web_view.invoke("missing", serde_json::Value::Null), Err("Unknown command `missing`".to_owned()));
+    assert_eq!(web_view.invoke("forget", serde_json::Value::Null), Err("The command didn't reply".to_owned()));
+}
+
+#[test]
+fn applies_navigation_policy() {
+    let mut web_view = WebViewBuilder::new()
+        .with_navigation_policy(|action: &NavigationAction| {
+            if action.url.starts_with("winit://") {
+                NavigationPolicy::Allow
+            } else if action.main_frame == Some(true) {
+                NavigationPolicy::OpenExternally
+            } else {
+                NavigationPolicy::Cancel
+            }
+        })
+        .build_headless(|_| {});
+
+    let action = NavigationAction {
+        url: "winit:///about.html".to_owned(),
+        navigation_type: NavigationType::LinkClicked,
+        main_frame: Some(true)
+    };
+    assert_eq!(web_view.decide_policy(&action), NavigationPolicy::Allow);
+
+    let action = NavigationAction { url: "https://example.com/".to_owned(), ..action };
+    assert_eq!(web_view.decide_policy(&action), NavigationPolicy::OpenExternally);
+
+    for main_frame in [Some(false), None] {
+        let action = NavigationAction { main_frame, ..action.clone() };
+        assert_eq!(web_view.decide_policy(&action), NavigationPolicy::Cancel);
+    }
+}
+
+#[test]
+#[should_panic(expected = "handled by the webview")]
+fn refuses_built_in_schemes() {
+    WebViewBuilder::new().with_scheme("HTTPS", |_: Request| None::<Response<std::io::Empty>>);
+}
+
+#[test]
+#[should_panic(expected = "reserved")]
+fn refuses_reserved_schemes() {
+    WebViewBuilder::new().with_scheme("winit-ipc", |_: Request| None::<Response<std::io::Empty>>);
+}
+
+#[test]
+#[should_panic(expected = "valid scheme")]
+fn refuses_invalid_schemes() {
+    WebViewBuilder::new().with_scheme("app:", |_: Request| None::<Response<std::io::Empty>>);
+}
+
+#[test]
+fn holds_events_while_loading() {
+    let mut web_view = WebViewBuilder::new().build_headless(|_| {});
+    web_view.complete_navigation();
+
+    // Nothing is held until the engine starts loading, e.g. if the policy cancels the navigation
+    web_view.navigate(NavigationTarget::Url("winit:///next.html"));
+    web_view.send_binary(vec![1]);
+    assert_eq!(web_view.evaluated_scripts().len(), 1);
+
+    web_view.start_navigation();
+    web_view.send_binary(vec![2]);
+    assert_eq!(web_view.evaluated_scripts().len(), 1);
+
+    web_view.complete_navigation();
+    assert_eq!(web_view.evaluated_scripts().len(), 2);
+}
+
+#[cfg(feature = "serde")]
+#[test]
+fn emits_payloads_as_script_literals() {
+    let mut web_view = WebViewBuilder::new().build_headless(|_| {});
+    web_view.complete_navigation();
+
+    web_view.emit("note", "</script>\u{2028}\"").unwrap();
+    web_view.emit("saved", serde_json::json!({ "path": "a.txt", "size": 3 })).unwrap();
+
+    assert_eq!(web_view.evaluated_scripts(), [
+        r#"window.winit.__emit("note", "</script>\u2028\"");"#,
+        r#"window.winit.__emit("saved", {"path":"a.txt","size":3});"#
+    ]);
+}
+
+#[cfg(feature = "serde")]
+#[test]
+fn emits_held_events_in_order() {
+    let mut web_view = WebViewBuilder::new().build_headless(|_| {});
+    web_view.navigate(NavigationTarget::Url("winit:///index.html"));
+    web_view.start_navigation();
+
+    for step in 1..=3 {
+        web_view.emit("progress", step).unwrap();
+    }
+    assert!(web_view.evaluated_scripts().is_empty());
+
+    web_view.complete_navigation();
+    assert_eq!(web_view.evaluated_scripts(), [
+        r#"window.winit.__emit("progress", 1);"#,
+        r#"window.winit.__emit("progress", 2);"#,
+        r#"window.winit.__emit("progress", 3);"#
+    ]);
+}
+
+#[test]
+fn delivers_held_events_when_loading_fails() {
+    let mut web_view = WebViewBuilder::new().build_headless(|_| {});
+    web_view.complete_navigation();
+
+    web_view.navigate(NavigationTarget::Url("winit:///missing.html"));
+    web_view.start_navigation();
+    web_view.send_binary(vec![1]);
+    assert!(web_view.evaluated_scripts().is_empty());
+
+    web_view.fail_navigation();
+    assert_eq!(web_view.evaluated_scripts().len(), 1);
+
+    web_view.send_binary(vec![2]);
+    assert_eq!(web_view.evaluated_scripts().len(), 2);
+}
+
+/// Get the token a `send_binary` script makes the page fetch
+fn binary_token(script: &str) -> &str {
+    script.split('"').nth(1).unwrap()
+}
+
+fn fetch_binary(web_view: &mut WebView<()>, token: &str, origin: Option<&str>) -> Option<request::Response<request::BoxedBody>> {
+    let mut request = Request::get(format!("winit-ipc://localhost/binary/{}", token));
+    if let Some(origin) = origin {
+        request = request.with_header("Origin", origin);
+    }
+
+    web_view.send_request(request)
+}
+
+#[test]
+fn accepts_binary_messages_from_app_pages() {
+    let (sender, receiver) = mpsc::channel();
+    let mut web_view = WebViewBuilder::new().with_scheme("app", |_: Request| None::<Response<std::io::Empty>>).build_headless(sender);
+
+    for url in ["winit:///index.html", "app://host/index.html"] {
+        web_view.navigate(NavigationTarget::Url(url));
+        web_view.post_binary(&[0, 1, 255]);
+        match receiver.try_recv() {
+            Ok(Event::BinaryMessage(bytes, Some(source))) => {
+                assert_eq!(bytes, [0, 1, 255]);
+                assert_eq!(source.url, url);
+                assert_eq!(source.main_frame, None);
+            }
+            event => panic!("unexpected event {:?}", event)
+        }
+    }
+
+    web_view.navigate(NavigationTarget::Url("https://example.com/"));
+    web_view.post_binary(&[1]);
+    assert!(receiver.try_recv().is_err());
+}
+
+#[test]
+fn refuses_binary_requests_from_other_origins() {
+    let mut web_view = WebViewBuilder::new().build_headless(|_| {});
+    web_view.complete_navigation();
+    web_view.send_binary(vec![1]);
+    let token = binary_token(&web_view.evaluated_scripts()[0]).to_owned();
+
+    assert_eq!(fetch_binary(&mut web_view, &token, Some("https://example.com")).unwrap().status, 403);
+    assert_eq!(fetch_binary(&mut web_view, &token, Some("null")).unwrap().status, 403);
+    assert_eq!(fetch_binary(&mut web_view, &token, None).unwrap().status, 403);
+
+    let request = Request::new("POST", "winit-ipc://localhost/message").with_header("Origin", "https://example.com");
+    assert_eq!(web_view.send_request(request).unwrap().status, 403);
+
+    // Refused requests don't use up the message
+    let response = fetch_binary(&mut web_view, &token, Some("winit://")).unwrap();
+    assert_eq!(response.headers.get("Access-Control-Allow-Origin"), Some("winit://"));
+    assert_eq!(read_body(response), "\u{1}");
+}
+
+#[test]
+fn fetches_binary_messages_once_by_token() {
+    let mut web_view = WebViewBuilder::new().with_base_url("https://app.example/").build_headless(|_| {});
+    web_view.complete_navigation();
+    web_view.send_binary(vec![9, 8, 7]);
+    web_view.send_binary(vec![6]);
+
+    let first = binary_token(&web_view.evaluated_scripts()[0]).to_owned();
+    let second = binary_token(&web_view.evaluated_scripts()[1]).to_owned();
+    assert_ne!(first, second);
+    assert_eq!(first.len(), 32);
+
+    let mut response = fetch_binary(&mut web_view, &first, Some("https://app.example")).unwrap();
+    let mut bytes = Vec::new();
+    response.body.read_to_end(&mut bytes).unwrap();
+    assert_eq!(bytes, [9, 8, 7]);
+
+    assert!(fetch_binary(&mut web_view, &first, Some("https://app.example")).is_none());
+    assert!(fetch_binary(&mut web_view, "1", Some("https://app.example")).is_none());
+    assert!(fetch_binary(&mut web_view, &second, Some("https://app.example")).is_some());
+}
+
+#[test]
+fn expires_binary_messages_when_leaving_a_page() {
+    let mut web_view = WebViewBuilder::new().build_headless(|_| {});
+    web_view.complete_navigation();
+    web_view.send_binary(vec![1]);
+    let unfetched = binary_token(&web_view.evaluated_scripts()[0]).to_owned();
+
+    web_view.navigate(NavigationTarget::Url("winit:///next.html"));
+    web_view.start_navigation();
+    assert!(fetch_binary(&mut web_view, &unfetched, Some("winit://")).is_none());
+
+    // Messages sent while the next page loads are kept for it
+    web_view.send_binary(vec![2]);
+    web_view.complete_navigation();
+    let token = binary_token(&web_view.evaluated_scripts()[1]).to_owned();
+    assert!(fetch_binary(&mut web_view, &token, Some("winit://")).is_some());
+}